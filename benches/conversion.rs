@@ -0,0 +1,70 @@
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage};
+use pixel_art_rust::core::color::AverageColorExtractor;
+use pixel_art_rust::core::grid::Grid;
+use pixel_art_rust::core::pixel_art::PixelArtConverter;
+use std::hint::black_box;
+
+const SIZE: u32 = 1024;
+
+fn gradient_rgba() -> RgbaImage {
+    RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+        Rgba([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8, 255])
+    })
+}
+
+fn gradient_rgb() -> RgbImage {
+    RgbImage::from_fn(SIZE, SIZE, |x, y| {
+        Rgb([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8])
+    })
+}
+
+// A 64x64 grid over a 1024x1024 image is 4096 cells; per-cell image copies dominate
+// unless the pixels are converted once per conversion
+fn grid_conversion(c: &mut Criterion) {
+    let inputs = [
+        ("rgba8", DynamicImage::ImageRgba8(gradient_rgba())),
+        ("rgb8", DynamicImage::ImageRgb8(gradient_rgb())),
+    ];
+    let mut group = c.benchmark_group("grid_64x64");
+    group.sample_size(10);
+
+    for (name, image) in &inputs {
+        let grid = Grid::new(SIZE, SIZE, 64, 64);
+        let converter = PixelArtConverter::with_grid(grid, Box::new(AverageColorExtractor));
+
+        group.bench_with_input(BenchmarkId::new("convert", name), image, |b, image| {
+            b.iter(|| converter.convert(black_box(image)).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("convert_parallel", name),
+            image,
+            |b, image| b.iter(|| converter.convert_parallel(black_box(image)).unwrap()),
+        );
+    }
+
+    group.finish();
+}
+
+// Large outputs, where writing the result dominates
+fn large_grid_conversion(c: &mut Criterion) {
+    const LARGE: u32 = 4096;
+    let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(LARGE, LARGE, |x, y| {
+        Rgba([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8, 255])
+    }));
+    let grid = Grid::new(LARGE, LARGE, 64, 64);
+    let converter = PixelArtConverter::with_grid(grid, Box::new(AverageColorExtractor));
+
+    let mut group = c.benchmark_group("grid_4096_64x64");
+    group.sample_size(10);
+    group.bench_function("convert", |b| {
+        b.iter(|| converter.convert(black_box(&image)).unwrap())
+    });
+    group.bench_function("convert_parallel", |b| {
+        b.iter(|| converter.convert_parallel(black_box(&image)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, grid_conversion, large_grid_conversion);
+criterion_main!(benches);
@@ -1,16 +1,61 @@
+use crate::core::color::parse_hex_color;
+use crate::core::dither::Dithering;
+use crate::core::grid::{GridSpec, PixelAspect, Rect};
+use crate::core::layout::{GridLayout, HexOrientation};
+use crate::core::palette::PaletteOrder;
 use anyhow::Result;
 use clap::{Parser, ValueEnum};
+use image::Rgba;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(name = "pixel-art-rust")]
 #[command(about = "Convert images to pixel art", long_about = None)]
 pub struct Args {
-    #[arg(short, long, help = "Number of horizontal divisions")]
-    pub width: u32,
+    #[arg(
+        short,
+        long,
+        requires = "height",
+        conflicts_with_all = ["cell_size", "target_width", "default_size"],
+        help = "Number of horizontal divisions"
+    )]
+    pub width: Option<u32>,
+
+    #[arg(long, requires = "width", help = "Number of vertical divisions")]
+    pub height: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "W[xH]",
+        value_parser = parse_cell_size,
+        conflicts_with_all = ["target_width", "default_size"],
+        help = "Cell size in source pixels, e.g. 8 or 8x12"
+    )]
+    pub cell_size: Option<(u32, u32)>,
+
+    #[arg(
+        long,
+        value_name = "COLUMNS",
+        conflicts_with = "default_size",
+        help = "Number of cells across; rows follow the image's aspect ratio"
+    )]
+    pub target_width: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Square cells, a fixed 64 along the longer side; does not look at the image content"
+    )]
+    pub default_size: bool,
 
-    #[arg(long, help = "Number of vertical divisions")]
-    pub height: u32,
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "width", "cell_size", "target_width", "default_size",
+            "offset_x", "offset_y", "crop", "adaptive", "stream",
+        ],
+        help = "Detect the pixel grid of upscaled pixel art and recover the native sprite"
+    )]
+    pub detect_grid: bool,
 
     #[arg(short, long, help = "Input image path")]
     pub input: PathBuf,
@@ -29,6 +74,54 @@ pub struct Args {
     #[arg(short, long, help = "Number of colors for quantization")]
     pub colors: Option<u32>,
 
+    #[arg(
+        long,
+        help = "Reduce all cell colors to one shared palette of --colors colors (default 16)"
+    )]
+    pub quantize: bool,
+
+    #[arg(
+        long = "lock-color",
+        value_name = "#RRGGBB",
+        value_parser = parse_hex_color,
+        help = "Color that must appear in the final palette (repeatable)"
+    )]
+    pub lock_colors: Vec<Rgba<u8>>,
+
+    #[arg(
+        long,
+        value_name = "STEPS",
+        help = "Build a hue-shifted shading ramp of this many steps per dominant color"
+    )]
+    pub ramps: Option<u32>,
+
+    #[arg(
+        long,
+        default_value = "20.0",
+        help = "Hue rotation in degrees at the ends of each ramp (when --ramps)"
+    )]
+    pub hue_shift: f32,
+
+    #[arg(
+        long,
+        value_name = "ORDER",
+        help = "Order of the palette, which exported palettes and indexed PNGs follow"
+    )]
+    pub palette_order: Option<PaletteOrderMode>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write the palette as a GIMP palette (.gpl) or one hex color per line"
+    )]
+    pub palette_out: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Save the output as an indexed PNG with the palette's order"
+    )]
+    pub indexed: bool,
+
     #[arg(long, help = "Use adaptive quadtree instead of uniform grid")]
     pub adaptive: bool,
 
@@ -45,6 +138,124 @@ pub struct Args {
         help = "Variance threshold for quadtree splitting"
     )]
     pub variance_threshold: f64,
+
+    #[arg(
+        long,
+        default_value = "variance",
+        help = "Quadtree split criterion (when --adaptive)"
+    )]
+    pub split_criterion: SplitMode,
+
+    #[arg(
+        long,
+        help = "Threshold for the split criterion [default: --variance-threshold for variance, 30 for max-delta, 10 for edge, 2 for entropy]"
+    )]
+    pub split_threshold: Option<f64>,
+
+    #[arg(
+        long,
+        default_value = "1",
+        value_name = "CELLS",
+        help = "Smallest quadtree node side, in pixels or in lattice cells with --lattice (when --adaptive)"
+    )]
+    pub min_node_size: u32,
+
+    #[arg(
+        long,
+        help = "Snap quadtree leaves to the cell lattice of -w/--width and --height, --cell-size, --target-width or --default-size (when --adaptive)"
+    )]
+    pub lattice: bool,
+
+    #[arg(
+        long,
+        help = "Keep adjacent quadtree leaves within a 2:1 size ratio (when --adaptive)"
+    )]
+    pub balance: bool,
+
+    #[arg(
+        long,
+        help = "Speed up grid mode by extracting runs of identical cells once"
+    )]
+    pub merge_uniform: bool,
+
+    #[arg(
+        long,
+        default_value = "none",
+        help = "Dithering when mapping cells to the palette (grid mode)"
+    )]
+    pub dither: DitherMode,
+
+    #[arg(
+        long,
+        help = "Output each cell as a SCALE x SCALE block instead of keeping the input size (grid mode)"
+    )]
+    pub scale: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "W:H",
+        default_value = "1:1",
+        value_parser = parse_pixel_aspect,
+        help = "Pixel aspect ratio of the target display, e.g. 2:1 for wide C64 pixels (grid mode)"
+    )]
+    pub par: PixelAspect,
+
+    #[arg(
+        long,
+        help = "Process the image in bands of cell rows and write PNG output row by row (grid mode)"
+    )]
+    pub stream: bool,
+
+    #[arg(
+        long,
+        default_value = "rect",
+        help = "Cell shape for grid mode; cells keep the grid's cell size"
+    )]
+    pub layout: LayoutMode,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "Start the grid this many pixels from the left edge of the image or crop"
+    )]
+    pub offset_x: u32,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "Start the grid this many pixels from the top edge of the image or crop"
+    )]
+    pub offset_y: u32,
+
+    #[arg(
+        long,
+        value_name = "WxH+X+Y",
+        value_parser = parse_crop,
+        help = "Convert only this region, e.g. 320x240+16+8"
+    )]
+    pub crop: Option<Rect>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write the quadtree nodes as JSON (when --adaptive)"
+    )]
+    pub tree_json: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Save the result with quadtree node borders colored by depth (when --adaptive)"
+    )]
+    pub overlay: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "IMAGE",
+        default_value = "result",
+        help = "Image the --overlay borders are drawn on"
+    )]
+    pub overlay_base: OverlayBase,
 }
 
 #[derive(ValueEnum, Clone, Debug, PartialEq, Eq)]
@@ -56,15 +267,171 @@ pub enum ColorAlgorithm {
     KMeans,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverlayBase {
+    // The input image, to judge where the tree split against the original detail
+    Source,
+    Result,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DitherMode {
+    None,
+    #[value(name = "floyd-steinberg")]
+    FloydSteinberg,
+    Ordered,
+}
+
+impl From<DitherMode> for Dithering {
+    fn from(mode: DitherMode) -> Self {
+        match mode {
+            DitherMode::None => Dithering::None,
+            DitherMode::FloydSteinberg => Dithering::FloydSteinberg,
+            DitherMode::Ordered => Dithering::Ordered,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteOrderMode {
+    Hue,
+    Luminance,
+    Similarity,
+    Ramps,
+}
+
+impl From<PaletteOrderMode> for PaletteOrder {
+    fn from(mode: PaletteOrderMode) -> Self {
+        match mode {
+            PaletteOrderMode::Hue => PaletteOrder::Hue,
+            PaletteOrderMode::Luminance => PaletteOrder::Luminance,
+            PaletteOrderMode::Similarity => PaletteOrder::SimilarityChain,
+            PaletteOrderMode::Ramps => PaletteOrder::Ramps,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayoutMode {
+    Rect,
+    #[value(name = "hex-pointy")]
+    HexPointy,
+    #[value(name = "hex-flat")]
+    HexFlat,
+    Diamond,
+    Brick,
+}
+
+impl From<LayoutMode> for GridLayout {
+    fn from(mode: LayoutMode) -> Self {
+        match mode {
+            LayoutMode::Rect => GridLayout::Rectangular,
+            LayoutMode::HexPointy => GridLayout::Hexagonal(HexOrientation::Pointy),
+            LayoutMode::HexFlat => GridLayout::Hexagonal(HexOrientation::Flat),
+            LayoutMode::Diamond => GridLayout::Diamond,
+            LayoutMode::Brick => GridLayout::Brick,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitMode {
+    Variance,
+    #[value(name = "max-delta")]
+    MaxDelta,
+    Edge,
+    Entropy,
+}
+
+// Accepts `8` for square cells or `8x12` for width x height
+fn parse_cell_size(value: &str) -> Result<(u32, u32), String> {
+    let side = |part: &str| part.trim().parse::<u32>().ok().filter(|&n| n > 0);
+    let size = match value.split_once(['x', 'X']) {
+        Some((width, height)) => side(width).zip(side(height)),
+        None => side(value).map(|n| (n, n)),
+    };
+    size.ok_or_else(|| format!("Invalid cell size '{value}': expected e.g. 8 or 8x12"))
+}
+
+// Accepts `W:H` with positive whole numbers, e.g. `2:1` or `8:7`
+fn parse_pixel_aspect(value: &str) -> Result<PixelAspect, String> {
+    let side = |part: &str| part.trim().parse::<u32>().ok().filter(|&n| n > 0);
+    value
+        .split_once(':')
+        .and_then(|(width, height)| side(width).zip(side(height)))
+        .map(|(width, height)| PixelAspect::new(width, height))
+        .ok_or_else(|| format!("Invalid pixel aspect '{value}': expected e.g. 2:1"))
+}
+
+// Accepts `WxH+X+Y`, or `WxH` for a region at the top left
+fn parse_crop(value: &str) -> Result<Rect, String> {
+    let number = |part: &str| part.trim().parse::<u32>().ok();
+    let (size, position) = match value.split_once('+') {
+        Some((size, position)) => (size, Some(position)),
+        None => (value, None),
+    };
+    let size = size
+        .split_once(['x', 'X'])
+        .and_then(|(width, height)| number(width).zip(number(height)))
+        .filter(|&(width, height)| width > 0 && height > 0);
+    let position = match position {
+        Some(position) => position
+            .split_once('+')
+            .and_then(|(x, y)| number(x).zip(number(y))),
+        None => Some((0, 0)),
+    };
+    match (size, position) {
+        (Some((width, height)), Some((x, y))) => Ok(Rect::new(x, y, width, height)),
+        _ => Err(format!(
+            "Invalid crop '{value}': expected e.g. 320x240+16+8"
+        )),
+    }
+}
+
 impl Args {
+    // The grid sizing option that was given, if any
+    pub fn grid_spec(&self) -> Option<GridSpec> {
+        if let (Some(columns), Some(rows)) = (self.width, self.height) {
+            Some(GridSpec::Cells { columns, rows })
+        } else if let Some((width, height)) = self.cell_size {
+            Some(GridSpec::CellSize { width, height })
+        } else if let Some(columns) = self.target_width {
+            Some(GridSpec::TargetWidth { columns })
+        } else if self.default_size {
+            Some(GridSpec::DefaultCells)
+        } else {
+            None
+        }
+    }
+
+    // `--colors` alone only sets the extractor's color count; a shared palette is opt-in
+    pub fn uses_palette(&self) -> bool {
+        self.quantize || !self.lock_colors.is_empty() || self.ramps.is_some()
+    }
+
     pub fn validate(&self) -> Result<()> {
         // Validate dimensions
-        if self.width == 0 {
+        if self.width == Some(0) {
             return Err(anyhow::anyhow!("Width must be greater than 0"));
         }
-        if self.height == 0 {
+        if self.height == Some(0) {
             return Err(anyhow::anyhow!("Height must be greater than 0"));
         }
+        if self
+            .cell_size
+            .is_some_and(|(width, height)| width == 0 || height == 0)
+        {
+            return Err(anyhow::anyhow!("Cell size must be greater than 0"));
+        }
+        if self.target_width == Some(0) {
+            return Err(anyhow::anyhow!("Target width must be greater than 0"));
+        }
+        // Adaptive mode only needs a grid to snap to with --lattice
+        if self.grid_spec().is_none() && !self.detect_grid && (!self.adaptive || self.lattice) {
+            return Err(anyhow::anyhow!(
+                "Choose a grid size with -w/--width and --height, --cell-size, --target-width, --default-size or --detect-grid"
+            ));
+        }
 
         // Validate file paths
         if self.input.as_os_str().is_empty() {
@@ -84,6 +451,34 @@ impl Args {
             }
         }
 
+        // Locked colors must fit into the palette
+        let palette_size = self.colors.unwrap_or(16) as usize;
+        if self.lock_colors.len() > palette_size {
+            return Err(anyhow::anyhow!(
+                "Cannot lock {} colors into a palette of {} colors",
+                self.lock_colors.len(),
+                palette_size
+            ));
+        }
+
+        if let Some(steps) = self.ramps {
+            if steps < 2 {
+                return Err(anyhow::anyhow!("Ramps need at least 2 steps"));
+            }
+            if steps as usize + self.lock_colors.len() > palette_size {
+                return Err(anyhow::anyhow!(
+                    "A ramp of {} steps does not fit into a palette of {} colors",
+                    steps,
+                    palette_size
+                ));
+            }
+            if !(0.0..=180.0).contains(&self.hue_shift) {
+                return Err(anyhow::anyhow!(
+                    "Hue shift must be between 0 and 180 degrees"
+                ));
+            }
+        }
+
         // Validate quadtree parameters when adaptive is enabled
         if self.adaptive {
             if self.max_depth == 0 {
@@ -102,6 +497,93 @@ impl Args {
             if self.variance_threshold > 255.0 {
                 return Err(anyhow::anyhow!("Variance threshold must be 255 or less"));
             }
+            if self
+                .split_threshold
+                .is_some_and(|threshold| threshold < 0.0)
+            {
+                return Err(anyhow::anyhow!("Split threshold must be non-negative"));
+            }
+            if self.min_node_size == 0 {
+                return Err(anyhow::anyhow!("Min node size must be greater than 0"));
+            }
+        } else if self.tree_json.is_some() || self.overlay.is_some() {
+            return Err(anyhow::anyhow!(
+                "--tree-json and --overlay require --adaptive"
+            ));
+        }
+        if self.overlay_base != OverlayBase::Result && self.overlay.is_none() {
+            return Err(anyhow::anyhow!("--overlay-base requires --overlay"));
+        }
+
+        if self.dither != DitherMode::None && !self.uses_palette() {
+            return Err(anyhow::anyhow!(
+                "--dither requires --quantize, --lock-color or --ramps"
+            ));
+        }
+        if self.scale == Some(0) {
+            return Err(anyhow::anyhow!("Scale must be greater than 0"));
+        }
+        if self.adaptive && (self.dither != DitherMode::None || self.scale.is_some()) {
+            return Err(anyhow::anyhow!(
+                "--dither and --scale cannot be used with --adaptive"
+            ));
+        }
+        if self.adaptive && self.merge_uniform {
+            return Err(anyhow::anyhow!(
+                "--merge-uniform works on the uniform grid and cannot be used with --adaptive"
+            ));
+        }
+        if self.adaptive && !self.par.is_square() {
+            return Err(anyhow::anyhow!("--par cannot be used with --adaptive"));
+        }
+
+        // Non-rectangular cells are painted at the input size, one pass over the image
+        if self.layout != LayoutMode::Rect {
+            if self.adaptive {
+                return Err(anyhow::anyhow!("--layout cannot be used with --adaptive"));
+            }
+            if self.scale.is_some() || self.merge_uniform || self.stream {
+                return Err(anyhow::anyhow!(
+                    "--scale, --merge-uniform and --stream need --layout rect"
+                ));
+            }
+        }
+
+        // Streaming only works band by band over a uniform grid and writes PNG
+        if self.stream {
+            if self.adaptive {
+                return Err(anyhow::anyhow!("--stream cannot be used with --adaptive"));
+            }
+            if self.ramps.is_some() {
+                return Err(anyhow::anyhow!("--stream cannot be used with --ramps"));
+            }
+            let is_png = self
+                .output
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+            if !is_png {
+                return Err(anyhow::anyhow!("--stream writes PNG output only"));
+            }
+        }
+
+        if (self.palette_order.is_some() || self.palette_out.is_some() || self.indexed)
+            && !self.uses_palette()
+        {
+            return Err(anyhow::anyhow!(
+                "--palette-order, --palette-out and --indexed need --quantize, --lock-color or --ramps"
+            ));
+        }
+        if self.indexed {
+            if self.stream {
+                return Err(anyhow::anyhow!("--indexed cannot be used with --stream"));
+            }
+            let is_png = self
+                .output
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+            if !is_png {
+                return Err(anyhow::anyhow!("--indexed writes PNG output only"));
+            }
         }
 
         Ok(())
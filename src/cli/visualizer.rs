@@ -140,6 +140,42 @@ impl GridVisualizer {
     }
 }
 
+// Adaptive mode has no fixed cells, so progress is the share of pixels covered by
+// finished quadtree leaves
+pub struct TreeVisualizer {
+    bar: ProgressBar,
+}
+
+impl TreeVisualizer {
+    pub fn new(total_pixels: u64) -> Self {
+        let bar = ProgressBar::new(total_pixels);
+        bar.set_style(create_tree_progress_style());
+        bar.set_message("Building quadtree");
+        bar.enable_steady_tick(std::time::Duration::from_millis(100));
+        Self { bar }
+    }
+
+    pub fn update(&self, done: u64, total: u64) {
+        if self.bar.length() != Some(total) {
+            self.bar.set_length(total);
+        }
+        self.bar.set_position(done);
+    }
+
+    pub fn finish(&self) {
+        self.bar
+            .finish_with_message("Quadtree processing completed");
+    }
+
+    pub fn completed_pixels(&self) -> u64 {
+        self.bar.position()
+    }
+
+    pub fn total_pixels(&self) -> u64 {
+        self.bar.length().unwrap_or(0)
+    }
+}
+
 pub fn create_main_progress_style() -> ProgressStyle {
     ProgressStyle::with_template(
         "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}",
@@ -151,3 +187,10 @@ pub fn create_grid_progress_style() -> ProgressStyle {
     ProgressStyle::with_template("Row {prefix:>3} [{bar:25.green/red}] {percent:>3}%")
         .unwrap_or_else(|_| ProgressStyle::default_bar())
 }
+
+pub fn create_tree_progress_style() -> ProgressStyle {
+    ProgressStyle::with_template(
+        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {percent:>3}% of {human_len} px ({eta}) {msg}",
+    )
+    .unwrap_or_else(|_| ProgressStyle::default_bar())
+}
@@ -1,19 +1,45 @@
+use crate::core::error::{PixelArtError, PixelArtResult};
+use crate::core::view::PixelView;
 use image::Rgba;
 use lazy_static::lazy_static;
 use palette::{FromColor, Lab, Srgb};
+use std::cell::RefCell;
 
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
 
+thread_local! {
+    static GATHER_BUFFER: RefCell<Vec<Rgba<u8>>> = RefCell::new(Vec::with_capacity(65536));
+}
+
 pub trait ColorExtractor: Send + Sync {
     fn extract_color(&self, pixels: &[Rgba<u8>]) -> Rgba<u8>;
+
+    // Extracts from a region of a larger image. The default copies non-contiguous views
+    // into a buffer for `extract_color`; extractors that can iterate rows in place override it.
+    fn extract_color_view(&self, view: PixelView<'_>) -> Rgba<u8> {
+        if let Some(pixels) = view.as_slice() {
+            return self.extract_color(pixels);
+        }
+
+        GATHER_BUFFER.with(|buffer| {
+            let mut buffer = buffer.borrow_mut();
+            buffer.clear();
+            buffer.extend(view.pixels());
+            self.extract_color(&buffer)
+        })
+    }
 }
 
 pub struct AverageColorExtractor;
 
 impl ColorExtractor for AverageColorExtractor {
     fn extract_color(&self, pixels: &[Rgba<u8>]) -> Rgba<u8> {
-        if pixels.is_empty() {
+        self.extract_color_view(PixelView::from_slice(pixels))
+    }
+
+    fn extract_color_view(&self, view: PixelView<'_>) -> Rgba<u8> {
+        if view.is_empty() {
             return Rgba([0, 0, 0, 255]);
         }
 
@@ -21,13 +47,15 @@ impl ColorExtractor for AverageColorExtractor {
         let mut total_g = 0u32;
         let mut total_b = 0u32;
 
-        for pixel in pixels {
-            total_r += pixel.0[0] as u32;
-            total_g += pixel.0[1] as u32;
-            total_b += pixel.0[2] as u32;
+        for row in view.rows() {
+            for pixel in row {
+                total_r += pixel.0[0] as u32;
+                total_g += pixel.0[1] as u32;
+                total_b += pixel.0[2] as u32;
+            }
         }
 
-        let count = pixels.len() as u32;
+        let count = view.len() as u32;
         Rgba([
             (total_r / count) as u8,
             (total_g / count) as u8,
@@ -43,16 +71,20 @@ pub struct MedianCutExtractor {
 
 impl ColorExtractor for MedianCutExtractor {
     fn extract_color(&self, pixels: &[Rgba<u8>]) -> Rgba<u8> {
-        if pixels.is_empty() {
+        self.extract_color_view(PixelView::from_slice(pixels))
+    }
+
+    fn extract_color_view(&self, view: PixelView<'_>) -> Rgba<u8> {
+        if view.is_empty() {
             return Rgba([0, 0, 0, 255]);
         }
 
-        if pixels.len() == 1 {
-            return pixels[0];
+        if view.len() == 1 {
+            return view.row(0)[0];
         }
 
         // Simple implementation: return the median color
-        let mut sorted_pixels = pixels.to_vec();
+        let mut sorted_pixels = view.to_vec();
         sorted_pixels.sort_by(|a, b| {
             let a_lum = (a.0[0] as u32 + a.0[1] as u32 + a.0[2] as u32) / 3;
             let b_lum = (b.0[0] as u32 + b.0[1] as u32 + b.0[2] as u32) / 3;
@@ -70,17 +102,22 @@ pub struct KMeansExtractor {
 
 impl ColorExtractor for KMeansExtractor {
     fn extract_color(&self, pixels: &[Rgba<u8>]) -> Rgba<u8> {
-        if pixels.is_empty() {
+        self.extract_color_view(PixelView::from_slice(pixels))
+    }
+
+    fn extract_color_view(&self, view: PixelView<'_>) -> Rgba<u8> {
+        if view.is_empty() {
             return Rgba([0, 0, 0, 255]);
         }
 
-        if pixels.len() == 1 {
-            return pixels[0];
+        let first = view.row(0)[0];
+        if view.len() == 1 {
+            return first;
         }
 
         // Simple implementation: return the most common color
         let mut color_counts = std::collections::HashMap::new();
-        for pixel in pixels {
+        for pixel in view.pixels() {
             *color_counts.entry(*pixel).or_insert(0) += 1;
         }
 
@@ -88,24 +125,32 @@ impl ColorExtractor for KMeansExtractor {
             .into_iter()
             .max_by_key(|(_, count)| *count)
             .map(|(color, _)| color)
-            .unwrap_or(pixels[0])
+            .unwrap_or(first)
+    }
+}
+
+// Accepts `#RRGGBB` or `RRGGBB`
+pub fn parse_hex_color(value: &str) -> PixelArtResult<Rgba<u8>> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(PixelArtError::InvalidColor(value.to_string()));
     }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0);
+    Ok(Rgba([channel(0), channel(2), channel(4), 255]))
+}
+
+pub fn rgba_to_lab(color: &Rgba<u8>) -> Lab {
+    Lab::from_color(Srgb::new(
+        color.0[0] as f32 / 255.0,
+        color.0[1] as f32 / 255.0,
+        color.0[2] as f32 / 255.0,
+    ))
 }
 
 pub fn color_distance_lab(color1: &Rgba<u8>, color2: &Rgba<u8>) -> f64 {
-    let rgb1 = Srgb::new(
-        color1.0[0] as f32 / 255.0,
-        color1.0[1] as f32 / 255.0,
-        color1.0[2] as f32 / 255.0,
-    );
-    let rgb2 = Srgb::new(
-        color2.0[0] as f32 / 255.0,
-        color2.0[1] as f32 / 255.0,
-        color2.0[2] as f32 / 255.0,
-    );
-
-    let lab1 = Lab::from_color(rgb1);
-    let lab2 = Lab::from_color(rgb2);
+    let lab1 = rgba_to_lab(color1);
+    let lab2 = rgba_to_lab(color2);
 
     let dl = lab1.l - lab2.l;
     let da = lab1.a - lab2.a;
@@ -290,23 +335,59 @@ pub fn color_distance_lab_fast(color1: &Rgba<u8>, color2: &Rgba<u8>) -> f64 {
     (dl * dl + da * da + db * db).sqrt() as f64
 }
 
+// L*a*b* (D65) through the gamma LUT; avoids the generic conversion in hot loops
+pub fn rgba_to_lab_values(color: &Rgba<u8>) -> [f32; 3] {
+    let r = GAMMA_LUT[color.0[0] as usize];
+    let g = GAMMA_LUT[color.0[1] as usize];
+    let b = GAMMA_LUT[color.0[2] as usize];
+
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = (0.0193339 * r + 0.119_192 * g + 0.9503041 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+pub fn lab_values_to_rgba(lab: [f32; 3]) -> Rgba<u8> {
+    let rgb = Srgb::from_color(Lab::new(lab[0], lab[1], lab[2]));
+    Rgba([
+        (rgb.red.clamp(0.0, 1.0) * 255.0).round() as u8,
+        (rgb.green.clamp(0.0, 1.0) * 255.0).round() as u8,
+        (rgb.blue.clamp(0.0, 1.0) * 255.0).round() as u8,
+        255,
+    ])
+}
+
 pub struct SoAAverageColorExtractor;
 
 impl ColorExtractor for SoAAverageColorExtractor {
     fn extract_color(&self, pixels: &[Rgba<u8>]) -> Rgba<u8> {
-        if pixels.is_empty() {
+        self.extract_color_view(PixelView::from_slice(pixels))
+    }
+
+    fn extract_color_view(&self, view: PixelView<'_>) -> Rgba<u8> {
+        if view.is_empty() {
             return Rgba([0, 0, 0, 255]);
         }
 
         // Separate RGBA channels for better cache locality
-        let len = pixels.len();
+        let len = view.len();
         let mut sum_r = 0u32;
         let mut sum_g = 0u32;
         let mut sum_b = 0u32;
 
         // Process in chunks for better cache utilization
         const CHUNK_SIZE: usize = 64;
-        for chunk in pixels.chunks(CHUNK_SIZE) {
+        for chunk in view.rows().flat_map(|row| row.chunks(CHUNK_SIZE)) {
             for pixel in chunk {
                 sum_r += pixel.0[0] as u32;
                 sum_g += pixel.0[1] as u32;
@@ -324,60 +405,278 @@ impl ColorExtractor for SoAAverageColorExtractor {
     }
 }
 
-pub fn hierarchical_color_clustering(
-    pixels: &[Rgba<u8>],
-    num_clusters: usize,
-) -> Vec<Vec<Rgba<u8>>> {
-    if pixels.is_empty() || num_clusters == 0 {
-        return vec![];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Linkage {
+    Ward,
+    Average,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DendrogramMerge {
+    // Node ids below `leaf_count` are leaves, id `leaf_count + i` is the cluster made by merge `i`
+    pub left: usize,
+    pub right: usize,
+    pub distance: f64,
+    pub weight: u32,
+}
+
+// Agglomerative clustering over the unique colors of an image, weighted by pixel count
+#[derive(Debug, Clone)]
+pub struct Dendrogram {
+    leaves: Vec<(Rgba<u8>, u32)>,
+    merges: Vec<DendrogramMerge>,
+}
+
+impl Dendrogram {
+    pub fn build(pixels: &[Rgba<u8>], linkage: Linkage) -> Self {
+        let mut histogram = std::collections::HashMap::new();
+        for pixel in pixels {
+            *histogram.entry(pixel.0).or_insert(0u32) += 1;
+        }
+
+        // Sorted leaves keep the result independent of pixel order
+        let mut leaves: Vec<(Rgba<u8>, u32)> = histogram
+            .into_iter()
+            .map(|(color, count)| (Rgba(color), count))
+            .collect();
+        leaves.sort_by_key(|(color, _)| color.0);
+
+        let merges = nn_chain_linkage(&leaves, linkage);
+        Self { leaves, merges }
     }
 
-    if num_clusters >= pixels.len() {
-        return pixels.iter().map(|&p| vec![p]).collect();
+    pub fn leaf_count(&self) -> usize {
+        self.leaves.len()
     }
 
-    // Simple implementation: divide pixels into clusters based on color similarity
-    let mut clusters: Vec<Vec<Rgba<u8>>> = Vec::new();
-    let mut remaining_pixels = pixels.to_vec();
+    pub fn leaves(&self) -> &[(Rgba<u8>, u32)] {
+        &self.leaves
+    }
 
-    for _ in 0..num_clusters {
-        if remaining_pixels.is_empty() {
-            break;
+    // Sorted by increasing distance
+    pub fn merges(&self) -> &[DendrogramMerge] {
+        &self.merges
+    }
+
+    // Leaf indices of each cluster when the tree is cut into `num_clusters` groups
+    pub fn cut(&self, num_clusters: usize) -> Vec<Vec<usize>> {
+        let n = self.leaves.len();
+        if n == 0 || num_clusters == 0 {
+            return vec![];
         }
 
-        let mut cluster = vec![remaining_pixels.remove(0)];
-        let cluster_center = cluster[0];
+        let merges_to_apply = n.saturating_sub(num_clusters);
+        let mut parent: Vec<usize> = (0..n).collect();
+        for merge in &self.merges[..merges_to_apply] {
+            let left = find_root(&mut parent, self.leaf_of(merge.left));
+            let right = find_root(&mut parent, self.leaf_of(merge.right));
+            parent[right.max(left)] = right.min(left);
+        }
 
-        remaining_pixels.retain(|&pixel| {
-            let distance = color_distance_lab_fast(&cluster_center, &pixel);
-            if distance < 40.0 {
-                cluster.push(pixel);
-                false
-            } else {
-                true
+        let mut clusters: Vec<Vec<usize>> = Vec::new();
+        let mut cluster_of_root = vec![usize::MAX; n];
+        for leaf in 0..n {
+            let root = find_root(&mut parent, leaf);
+            if cluster_of_root[root] == usize::MAX {
+                cluster_of_root[root] = clusters.len();
+                clusters.push(Vec::new());
             }
-        });
+            clusters[cluster_of_root[root]].push(leaf);
+        }
+        clusters
+    }
 
-        clusters.push(cluster);
+    // Pixel-weighted mean color of each cluster at the given cut
+    pub fn cluster_colors(&self, num_clusters: usize) -> Vec<Rgba<u8>> {
+        self.cut(num_clusters)
+            .iter()
+            .map(|cluster| {
+                let mut sums = [0u64; 3];
+                let mut total = 0u64;
+                for &leaf in cluster {
+                    let (color, count) = self.leaves[leaf];
+                    for (sum, channel) in sums.iter_mut().zip(color.0) {
+                        *sum += channel as u64 * count as u64;
+                    }
+                    total += count as u64;
+                }
+                Rgba([
+                    (sums[0] / total) as u8,
+                    (sums[1] / total) as u8,
+                    (sums[2] / total) as u8,
+                    255,
+                ])
+            })
+            .collect()
     }
 
-    // Add remaining pixels to the nearest cluster
-    for pixel in remaining_pixels {
-        let mut min_distance = f64::MAX;
-        let mut best_cluster = 0;
-
-        for (i, cluster) in clusters.iter().enumerate() {
-            if !cluster.is_empty() {
-                let distance = color_distance_lab_fast(&cluster[0], &pixel);
-                if distance < min_distance {
-                    min_distance = distance;
-                    best_cluster = i;
+    // Any leaf contained in the given node
+    fn leaf_of(&self, node: usize) -> usize {
+        let mut node = node;
+        while node >= self.leaves.len() {
+            node = self.merges[node - self.leaves.len()].left;
+        }
+        node
+    }
+}
+
+fn find_root(parent: &mut [usize], mut node: usize) -> usize {
+    while parent[node] != node {
+        parent[node] = parent[parent[node]];
+        node = parent[node];
+    }
+    node
+}
+
+// Nearest-neighbour chain with Lance-Williams updates: O(n²) time and memory in unique colors
+fn nn_chain_linkage(leaves: &[(Rgba<u8>, u32)], linkage: Linkage) -> Vec<DendrogramMerge> {
+    let n = leaves.len();
+    if n < 2 {
+        return vec![];
+    }
+
+    let labs: Vec<Lab> = leaves.iter().map(|(color, _)| rgba_to_lab(color)).collect();
+    let index = |i: usize, j: usize| {
+        let (i, j) = if i < j { (i, j) } else { (j, i) };
+        i * n - i * (i + 1) / 2 + (j - i - 1)
+    };
+
+    let mut weights: Vec<f64> = leaves.iter().map(|(_, count)| *count as f64).collect();
+
+    // Ward starts from twice the variance increase of each merge, 2·ni·nj/(ni+nj)·‖ci−cj‖²,
+    // which the Lance-Williams update below keeps exact; it equals the squared distance for
+    // single pixels. Average linkage works on plain distances.
+    let mut distances = vec![0f64; n * (n - 1) / 2];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let dl = (labs[i].l - labs[j].l) as f64;
+            let da = (labs[i].a - labs[j].a) as f64;
+            let db = (labs[i].b - labs[j].b) as f64;
+            let squared = dl * dl + da * da + db * db;
+            distances[index(i, j)] = match linkage {
+                Linkage::Ward => {
+                    2.0 * weights[i] * weights[j] / (weights[i] + weights[j]) * squared
                 }
+                Linkage::Average => squared.sqrt(),
+            };
+        }
+    }
+
+    let mut active = vec![true; n];
+    let mut raw_merges: Vec<(usize, usize, f64)> = Vec::with_capacity(n - 1);
+    let mut chain: Vec<usize> = Vec::new();
+
+    while raw_merges.len() < n - 1 {
+        if chain.is_empty() {
+            chain.push(active.iter().position(|&a| a).unwrap_or(0));
+        }
+
+        let current = chain[chain.len() - 1];
+        let previous = chain.len().checked_sub(2).map(|i| chain[i]);
+
+        // Prefer the previous chain element on ties so the chain always terminates
+        let mut nearest = previous.unwrap_or(usize::MAX);
+        let mut nearest_distance = previous.map_or(f64::INFINITY, |p| distances[index(current, p)]);
+        for other in 0..n {
+            if other == current || !active[other] {
+                continue;
             }
+            let distance = distances[index(current, other)];
+            if distance < nearest_distance {
+                nearest = other;
+                nearest_distance = distance;
+            }
+        }
+
+        if Some(nearest) != previous {
+            chain.push(nearest);
+            continue;
+        }
+
+        chain.truncate(chain.len() - 2);
+        let (keep, drop) = (current.min(nearest), current.max(nearest));
+        let (weight_keep, weight_drop) = (weights[keep], weights[drop]);
+
+        for other in 0..n {
+            if other == keep || other == drop || !active[other] {
+                continue;
+            }
+            let d_keep = distances[index(keep, other)];
+            let d_drop = distances[index(drop, other)];
+            distances[index(keep, other)] = match linkage {
+                Linkage::Ward => {
+                    let weight_other = weights[other];
+                    ((weight_keep + weight_other) * d_keep + (weight_drop + weight_other) * d_drop
+                        - weight_other * nearest_distance)
+                        / (weight_keep + weight_drop + weight_other)
+                }
+                Linkage::Average => {
+                    (weight_keep * d_keep + weight_drop * d_drop) / (weight_keep + weight_drop)
+                }
+            };
         }
 
-        clusters[best_cluster].push(pixel);
+        active[drop] = false;
+        weights[keep] += weight_drop;
+
+        let reported = match linkage {
+            Linkage::Ward => nearest_distance.max(0.0).sqrt(),
+            Linkage::Average => nearest_distance,
+        };
+        raw_merges.push((keep, drop, reported));
+    }
+
+    // NN-chain finds merges out of order; sort them and relabel the clusters
+    raw_merges.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+    let mut parent: Vec<usize> = (0..n).collect();
+    let mut node_id: Vec<usize> = (0..n).collect();
+    let mut weight_of: Vec<u32> = leaves.iter().map(|(_, count)| *count).collect();
+
+    raw_merges
+        .into_iter()
+        .enumerate()
+        .map(|(i, (a, b, distance))| {
+            let root_a = find_root(&mut parent, a);
+            let root_b = find_root(&mut parent, b);
+            let (left, right) = (node_id[root_a], node_id[root_b]);
+            let weight = weight_of[root_a] + weight_of[root_b];
+
+            let (root, child) = (root_a.min(root_b), root_a.max(root_b));
+            parent[child] = root;
+            node_id[root] = n + i;
+            weight_of[root] = weight;
+
+            DendrogramMerge {
+                left: left.min(right),
+                right: left.max(right),
+                distance,
+                weight,
+            }
+        })
+        .collect()
+}
+
+pub fn hierarchical_color_clustering(
+    pixels: &[Rgba<u8>],
+    num_clusters: usize,
+) -> Vec<Vec<Rgba<u8>>> {
+    if pixels.is_empty() || num_clusters == 0 {
+        return vec![];
     }
 
-    clusters
+    let dendrogram = Dendrogram::build(pixels, Linkage::Ward);
+    dendrogram
+        .cut(num_clusters)
+        .into_iter()
+        .map(|cluster| {
+            cluster
+                .into_iter()
+                .flat_map(|leaf| {
+                    let (color, count) = dendrogram.leaves[leaf];
+                    std::iter::repeat_n(color, count as usize)
+                })
+                .collect()
+        })
+        .collect()
 }
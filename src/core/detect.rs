@@ -0,0 +1,341 @@
+use crate::core::error::{PixelArtError, PixelArtResult};
+use crate::core::grid::{Grid, GridSpec};
+use image::{DynamicImage, RgbaImage};
+use rayon::prelude::*;
+use std::f64::consts::TAU;
+
+// Harmonics of the cell frequency scored per candidate period. Sharp cell edges put
+// energy into all of them, while periodic content inside the sprite rarely does.
+const HARMONICS: u32 = 3;
+// Candidate frequencies are a quarter of a DFT bin apart, then refined 16x around the best
+const SCAN_STEPS_PER_BIN: f64 = 4.0;
+const REFINE_STEPS: u32 = 16;
+// A longer period wins over the best one when it scores at least this well, so the
+// fundamental is picked instead of one of its harmonics
+const FUNDAMENTAL_RATIO: f64 = 0.8;
+
+// The pixel grid of upscaled pixel art, in source pixels. Cell sizes are fractional when
+// the art was scaled by a non-integer factor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectedGrid {
+    pub cell_width: f64,
+    pub cell_height: f64,
+    // Position of the first cell boundary; slightly negative when the first cell starts
+    // at the image edge
+    pub offset_x: f64,
+    pub offset_y: f64,
+    // Whole cells from the offset to the image edge
+    pub columns: u32,
+    pub rows: u32,
+    // Share of the image's edge energy that lies on the detected cell boundaries, beyond
+    // what evenly spread edges would give: 1 for clean upscales, near 0 for photos
+    pub confidence: f64,
+}
+
+impl DetectedGrid {
+    // Cells that are whole pixels, give or take resampling error
+    pub fn is_integral(&self) -> bool {
+        let whole = |size: f64| (size - size.round()).abs() < 0.05;
+        whole(self.cell_width) && whole(self.cell_height)
+    }
+
+    pub fn offset(&self) -> (u32, u32) {
+        let whole = |offset: f64| offset.round().max(0.0) as u32;
+        (whole(self.offset_x), whole(self.offset_y))
+    }
+
+    pub fn spec(&self) -> GridSpec {
+        GridSpec::CellSize {
+            width: (self.cell_width.round() as u32).max(1),
+            height: (self.cell_height.round() as u32).max(1),
+        }
+    }
+
+    // The detected cells, starting at `offset`
+    pub fn grid(&self) -> Grid {
+        let GridSpec::CellSize { width, height } = self.spec() else {
+            unreachable!("spec is always a cell size");
+        };
+        Grid::with_cell_size(self.columns * width, self.rows * height, width, height)
+    }
+
+    // Cells must be whole pixels for `grid`. With a non-integer scale factor, `image` is
+    // resampled so each cell becomes its rounded size, and the grid is returned in the
+    // resampled image's coordinates. Every output pixel is copied from evenly spaced
+    // points inside its source cell, so no blended colors appear; with pixels of
+    // neighbouring cells leaking in at the boundaries, extracting each cell's most
+    // common color recovers the sprite best.
+    pub fn resample(&self, image: &DynamicImage) -> (DynamicImage, DetectedGrid) {
+        if self.is_integral() {
+            return (image.clone(), *self);
+        }
+
+        let source = image.to_rgba8();
+        let x_axis = ResampledAxis::new(self.cell_width, self.offset_x, source.width());
+        let y_axis = ResampledAxis::new(self.cell_height, self.offset_y, source.height());
+        let resampled = RgbaImage::from_fn(x_axis.length, y_axis.length, |x, y| {
+            *source.get_pixel(x_axis.source(x), y_axis.source(y))
+        });
+
+        let detected = DetectedGrid {
+            cell_width: x_axis.cell,
+            cell_height: y_axis.cell,
+            offset_x: x_axis.offset,
+            offset_y: y_axis.offset,
+            ..*self
+        };
+        (DynamicImage::ImageRgba8(resampled), detected)
+    }
+}
+
+// Maps one axis of a resampled image back to the source, so that cells of `period`
+// source pixels become whole `cell` pixel cells starting at a whole `offset`
+struct ResampledAxis {
+    period: f64,
+    phase: f64,
+    cell: f64,
+    offset: f64,
+    length: u32,
+    source_length: u32,
+}
+
+impl ResampledAxis {
+    fn new(period: f64, phase: f64, source_length: u32) -> Self {
+        let cell = period.round().max(1.0);
+        let scale = cell / period;
+        Self {
+            period,
+            phase,
+            cell,
+            offset: (phase * scale).round().max(0.0),
+            length: ((source_length as f64 * scale).round() as u32).max(1),
+            source_length,
+        }
+    }
+
+    fn source(&self, position: u32) -> u32 {
+        let source = self.phase + (position as f64 + 0.5 - self.offset) * self.period / self.cell;
+        source.floor().clamp(0.0, (self.source_length - 1) as f64) as u32
+    }
+}
+
+// Finds the pixel grid of upscaled pixel art from the periodicity of its edges: color
+// changes between neighbouring pixels are summed into one profile per axis, and the
+// period and phase are read off its Fourier spectrum
+#[derive(Debug, Clone)]
+pub struct GridDetector {
+    pub min_cell: f64,
+    pub max_cell: f64,
+    pub min_confidence: f64,
+}
+
+impl Default for GridDetector {
+    fn default() -> Self {
+        Self {
+            min_cell: 2.0,
+            max_cell: 64.0,
+            min_confidence: 0.3,
+        }
+    }
+}
+
+impl GridDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Cell sizes to consider, in source pixels; at least 2
+    pub fn with_cell_range(mut self, min_cell: f64, max_cell: f64) -> Self {
+        self.min_cell = min_cell.max(2.0);
+        self.max_cell = max_cell.max(self.min_cell);
+        self
+    }
+
+    pub fn with_min_confidence(mut self, min_confidence: f64) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+
+    pub fn detect(&self, image: &RgbaImage) -> PixelArtResult<DetectedGrid> {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Err(PixelArtError::ZeroSizedImage { width, height });
+        }
+
+        let horizontal = self.fit_axis(&edge_profile(image, true), "horizontal")?;
+        let vertical = self.fit_axis(&edge_profile(image, false), "vertical")?;
+        let confidence = horizontal.confidence.min(vertical.confidence);
+        if confidence < self.min_confidence {
+            return Err(PixelArtError::GridNotDetected(format!(
+                "best fit of {:.2}x{:.2} pixel cells has confidence {:.2}, below {:.2}",
+                horizontal.period, vertical.period, confidence, self.min_confidence
+            )));
+        }
+
+        let cells = |length: u32, fit: &AxisFit| {
+            ((length as f64 - fit.phase) / fit.period + 0.01)
+                .floor()
+                .max(0.0) as u32
+        };
+        Ok(DetectedGrid {
+            cell_width: horizontal.period,
+            cell_height: vertical.period,
+            offset_x: horizontal.phase,
+            offset_y: vertical.phase,
+            columns: cells(width, &horizontal),
+            rows: cells(height, &vertical),
+            confidence,
+        })
+    }
+
+    fn fit_axis(&self, profile: &[f64], axis: &str) -> PixelArtResult<AxisFit> {
+        let length = profile.len() as f64 + 1.0;
+        // At least four cells along the axis are needed to see a period
+        let max_cell = self.max_cell.min(length / 4.0);
+        if max_cell < self.min_cell {
+            return Err(PixelArtError::GridNotDetected(format!(
+                "image is too small along the {axis} axis"
+            )));
+        }
+
+        let mean = profile.iter().sum::<f64>() / profile.len() as f64;
+        if mean <= 0.0 {
+            return Err(PixelArtError::GridNotDetected(format!(
+                "no {axis} edges in the image"
+            )));
+        }
+        let centered: Vec<f64> = profile.iter().map(|value| value - mean).collect();
+
+        // Scan evenly in frequency, where spectral peaks have constant width
+        let step = 1.0 / (length * SCAN_STEPS_PER_BIN);
+        let (low, high) = (1.0 / max_cell, 1.0 / self.min_cell);
+        let count = ((high - low) / step).ceil() as usize + 1;
+        let scores: Vec<f64> = (0..count)
+            .into_par_iter()
+            .map(|i| harmonic_score(&centered, 1.0 / (low + i as f64 * step).min(high)))
+            .collect();
+
+        let best = scores.iter().cloned().fold(0.0, f64::max);
+        // The lowest-frequency strong peak, i.e. the longest period
+        let peak = (0..count)
+            .find(|&i| {
+                let left = i == 0 || scores[i - 1] <= scores[i];
+                let right = i + 1 == count || scores[i + 1] <= scores[i];
+                left && right && scores[i] >= best * FUNDAMENTAL_RATIO
+            })
+            .unwrap_or(0);
+
+        let coarse = low + peak as f64 * step;
+        let period = (0..=2 * REFINE_STEPS)
+            .map(|i| {
+                let frequency = coarse + (i as f64 / REFINE_STEPS as f64 - 1.0) * step;
+                1.0 / frequency.clamp(low, high)
+            })
+            .max_by(|a, b| harmonic_score(&centered, *a).total_cmp(&harmonic_score(&centered, *b)))
+            .unwrap_or(1.0 / coarse);
+
+        // The fundamental's angle gives the position of the boundaries
+        let (re, im) = fourier(&centered, period);
+        let mut phase = (-im.atan2(re) / TAU * period).rem_euclid(period);
+        if phase > period - 0.5 {
+            phase -= period;
+        }
+
+        Ok(AxisFit {
+            period,
+            phase,
+            confidence: boundary_confidence(profile, period, phase),
+        })
+    }
+}
+
+struct AxisFit {
+    period: f64,
+    phase: f64,
+    confidence: f64,
+}
+
+// Summed color change across each boundary along one axis; entry `i` is the boundary
+// between pixels `i` and `i + 1`
+fn edge_profile(image: &RgbaImage, horizontal: bool) -> Vec<f64> {
+    let (width, height) = image.dimensions();
+    let (length, across) = if horizontal {
+        (width, height)
+    } else {
+        (height, width)
+    };
+    let pixel = |along: u32, other: u32| {
+        if horizontal {
+            image.get_pixel(along, other)
+        } else {
+            image.get_pixel(other, along)
+        }
+    };
+
+    (1..length)
+        .into_par_iter()
+        .map(|along| {
+            (0..across)
+                .map(|other| {
+                    let (a, b) = (pixel(along - 1, other), pixel(along, other));
+                    (0..3)
+                        .map(|c| (a.0[c] as i32 - b.0[c] as i32).unsigned_abs())
+                        .sum::<u32>() as f64
+                })
+                .sum()
+        })
+        .collect()
+}
+
+// Fourier coefficient of `profile` at frequency `1 / period`, with entry `i` placed at
+// boundary position `i + 1`
+fn fourier(profile: &[f64], period: f64) -> (f64, f64) {
+    let angle = TAU / period;
+    let (step_cos, step_sin) = (angle.cos(), angle.sin());
+    // Rotate incrementally instead of calling sin/cos per entry
+    let (mut cos, mut sin) = (step_cos, step_sin);
+    let (mut re, mut im) = (0.0, 0.0);
+    for &value in profile {
+        re += value * cos;
+        im -= value * sin;
+        (cos, sin) = (
+            cos * step_cos - sin * step_sin,
+            sin * step_cos + cos * step_sin,
+        );
+    }
+    (re, im)
+}
+
+// Mean spectral magnitude at the harmonics of `period` below the Nyquist frequency
+fn harmonic_score(profile: &[f64], period: f64) -> f64 {
+    let harmonics: Vec<f64> = (1..=HARMONICS)
+        .filter(|&k| period >= 2.0 * k as f64)
+        .map(|k| {
+            let (re, im) = fourier(profile, period / k as f64);
+            re.hypot(im)
+        })
+        .collect();
+    harmonics.iter().sum::<f64>() / harmonics.len().max(1) as f64
+}
+
+fn boundary_confidence(profile: &[f64], period: f64, phase: f64) -> f64 {
+    let window = (period * 0.2).max(0.5);
+    let mut near = 0usize;
+    let (mut near_energy, mut total_energy) = (0.0, 0.0);
+    for (i, &value) in profile.iter().enumerate() {
+        let offset = (i as f64 + 1.0 - phase).rem_euclid(period);
+        let distance = offset.min(period - offset);
+        if distance <= window {
+            near += 1;
+            near_energy += value;
+        }
+        total_energy += value;
+    }
+
+    // Evenly spread edges would put `expected` of the energy near the boundaries
+    let expected = near as f64 / profile.len() as f64;
+    if total_energy <= 0.0 || expected >= 1.0 {
+        return 0.0;
+    }
+    ((near_energy / total_energy - expected) / (1.0 - expected)).clamp(0.0, 1.0)
+}
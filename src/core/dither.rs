@@ -0,0 +1,111 @@
+use crate::core::quantization::map_to_palette;
+use image::Rgba;
+
+// Threshold map for ordered dithering, values 0..16
+const BAYER_4X4: [[f32; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dithering {
+    #[default]
+    None,
+    // Error diffusion; smooth gradients, irregular texture
+    FloydSteinberg,
+    // 4x4 Bayer pattern; the regular cross-hatch of classic pixel art
+    Ordered,
+}
+
+// Maps a row-major grid of cell colors onto `palette`. With dithering, each cell is
+// nudged so that neighbouring cells average out to the original tone.
+pub fn dither_to_palette(
+    colors: &mut [Rgba<u8>],
+    columns: usize,
+    palette: &[Rgba<u8>],
+    dithering: Dithering,
+) {
+    if palette.is_empty() || columns == 0 {
+        return;
+    }
+
+    match dithering {
+        Dithering::None => {
+            for color in colors.iter_mut() {
+                *color = map_to_palette(color, palette);
+            }
+        }
+        Dithering::FloydSteinberg => floyd_steinberg(colors, columns, palette),
+        Dithering::Ordered => ordered(colors, columns, palette),
+    }
+}
+
+fn floyd_steinberg(colors: &mut [Rgba<u8>], columns: usize, palette: &[Rgba<u8>]) {
+    let rows = colors.len() / columns;
+    let mut values: Vec<[f32; 3]> = colors
+        .iter()
+        .map(|color| [color.0[0] as f32, color.0[1] as f32, color.0[2] as f32])
+        .collect();
+
+    for row in 0..rows {
+        for col in 0..columns {
+            let index = row * columns + col;
+            let wanted = values[index];
+            let alpha = colors[index].0[3];
+            let mapped = map_to_palette(&to_rgba(wanted, alpha), palette);
+            colors[index] = mapped;
+
+            let error = [
+                wanted[0] - mapped.0[0] as f32,
+                wanted[1] - mapped.0[1] as f32,
+                wanted[2] - mapped.0[2] as f32,
+            ];
+            let mut spread = |r: usize, c: usize, weight: f32| {
+                let value = &mut values[r * columns + c];
+                for (channel, err) in value.iter_mut().zip(error) {
+                    *channel += err * weight;
+                }
+            };
+            if col + 1 < columns {
+                spread(row, col + 1, 7.0 / 16.0);
+            }
+            if row + 1 < rows {
+                if col > 0 {
+                    spread(row + 1, col - 1, 3.0 / 16.0);
+                }
+                spread(row + 1, col, 5.0 / 16.0);
+                if col + 1 < columns {
+                    spread(row + 1, col + 1, 1.0 / 16.0);
+                }
+            }
+        }
+    }
+}
+
+fn ordered(colors: &mut [Rgba<u8>], columns: usize, palette: &[Rgba<u8>]) {
+    // Roughly the gap between neighbouring palette entries along each channel
+    let step = 255.0 / (palette.len() as f32).cbrt().max(1.0);
+
+    for (index, color) in colors.iter_mut().enumerate() {
+        let (row, col) = (index / columns, index % columns);
+        let offset = (BAYER_4X4[row % 4][col % 4] + 0.5) / 16.0 - 0.5;
+        let nudged = [
+            color.0[0] as f32 + offset * step,
+            color.0[1] as f32 + offset * step,
+            color.0[2] as f32 + offset * step,
+        ];
+        *color = map_to_palette(&to_rgba(nudged, color.0[3]), palette);
+    }
+}
+
+fn to_rgba(values: [f32; 3], alpha: u8) -> Rgba<u8> {
+    let channel = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+    Rgba([
+        channel(values[0]),
+        channel(values[1]),
+        channel(values[2]),
+        alpha,
+    ])
+}
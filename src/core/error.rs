@@ -0,0 +1,73 @@
+use thiserror::Error;
+
+pub type PixelArtResult<T> = std::result::Result<T, PixelArtError>;
+
+#[derive(Debug, Error)]
+pub enum PixelArtError {
+    #[error("Grid must have at least one cell, got {columns}x{rows}")]
+    InvalidGrid { columns: u32, rows: u32 },
+
+    #[error("Image has no pixels ({width}x{height})")]
+    ZeroSizedImage { width: u32, height: u32 },
+
+    #[error("{columns}x{rows} grid does not fit a {width}x{height} image")]
+    GridLargerThanImage {
+        columns: u32,
+        rows: u32,
+        width: u32,
+        height: u32,
+    },
+
+    #[error("Cell size must be positive, got {width}x{height}")]
+    InvalidCellSize { width: u32, height: u32 },
+
+    #[error("{cell_width}x{cell_height} cells are larger than the {width}x{height} image")]
+    CellLargerThanImage {
+        cell_width: u32,
+        cell_height: u32,
+        width: u32,
+        height: u32,
+    },
+
+    #[error(
+        "{width}x{height} region at ({x}, {y}) is outside the {image_width}x{image_height} image"
+    )]
+    RegionOutsideImage {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        image_width: u32,
+        image_height: u32,
+    },
+
+    #[error("No pixel grid detected: {0}")]
+    GridNotDetected(String),
+
+    #[error("Invalid converter configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Unsupported pixel format: {0}")]
+    UnsupportedPixelFormat(String),
+
+    #[error("Streaming conversion needs a uniform grid")]
+    StreamingRequiresGrid,
+
+    #[error("Indexed PNG holds at most 256 colors, got {colors}")]
+    TooManyColors { colors: usize },
+
+    #[error("Invalid color '{0}': expected hex format #RRGGBB")]
+    InvalidColor(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("PNG decoding failed: {0}")]
+    PngDecoding(#[from] png::DecodingError),
+
+    #[error("PNG encoding failed: {0}")]
+    PngEncoding(#[from] png::EncodingError),
+
+    #[error("JSON export failed: {0}")]
+    Json(#[from] serde_json::Error),
+}
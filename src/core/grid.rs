@@ -1,3 +1,5 @@
+use crate::core::error::{PixelArtError, PixelArtResult};
+
 #[derive(Debug, Clone)]
 pub struct Grid {
     width: u32,
@@ -8,16 +10,8 @@ pub struct Grid {
 
 impl Grid {
     pub fn new(image_width: u32, image_height: u32, grid_width: u32, grid_height: u32) -> Self {
-        let cell_width = if grid_width > 0 {
-            image_width / grid_width
-        } else {
-            0
-        };
-        let cell_height = if grid_height > 0 {
-            image_height / grid_height
-        } else {
-            0
-        };
+        let cell_width = image_width.checked_div(grid_width).unwrap_or(0);
+        let cell_height = image_height.checked_div(grid_height).unwrap_or(0);
 
         Self {
             width: grid_width,
@@ -57,3 +51,230 @@ impl Grid {
         self.cell_height
     }
 }
+
+impl Grid {
+    // As many whole `cell_width` x `cell_height` cells as fit in the image
+    pub fn with_cell_size(
+        image_width: u32,
+        image_height: u32,
+        cell_width: u32,
+        cell_height: u32,
+    ) -> Self {
+        Self {
+            width: image_width.checked_div(cell_width).unwrap_or(0),
+            height: image_height.checked_div(cell_height).unwrap_or(0),
+            cell_width,
+            cell_height,
+        }
+    }
+}
+
+// A rectangle of image pixels, used for crop regions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn fits_within(&self, width: u32, height: u32) -> bool {
+        self.x as u64 + self.width as u64 <= width as u64
+            && self.y as u64 + self.height as u64 <= height as u64
+    }
+}
+
+// Where a grid goes in a `width` x `height` image: the frame (the crop, or the whole
+// image) that sizes the output, and the area inside it that starts `offset` pixels in.
+// Both must be non-empty and inside the image.
+pub fn grid_region(
+    width: u32,
+    height: u32,
+    crop: Option<Rect>,
+    offset: (u32, u32),
+) -> PixelArtResult<(Rect, Rect)> {
+    let frame = crop.unwrap_or(Rect::new(0, 0, width, height));
+    let (offset_x, offset_y) = offset;
+    let area = Rect::new(
+        frame.x.saturating_add(offset_x),
+        frame.y.saturating_add(offset_y),
+        frame.width.saturating_sub(offset_x),
+        frame.height.saturating_sub(offset_y),
+    );
+    for region in [frame, area] {
+        if region.width == 0 || region.height == 0 || !region.fits_within(width, height) {
+            return Err(PixelArtError::RegionOutsideImage {
+                x: region.x,
+                y: region.y,
+                width: region.width,
+                height: region.height,
+                image_width: width,
+                image_height: height,
+            });
+        }
+    }
+    Ok((frame, area))
+}
+
+// Cells along the longer side for `GridSpec::DefaultCells`, whatever the image shows
+pub const DEFAULT_GRID_CELLS: u32 = 64;
+
+// Width:height of one output pixel on the target display, e.g. 2:1 for C64 multicolor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelAspect {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PixelAspect {
+    pub const SQUARE: PixelAspect = PixelAspect {
+        width: 1,
+        height: 1,
+    };
+
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    pub fn ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.width == self.height
+    }
+
+    // Width of a pixel `height` units tall, at least 1
+    pub fn scaled_width(&self, height: u32) -> u32 {
+        ((height as f64 * self.ratio()).round() as u32).max(1)
+    }
+}
+
+impl Default for PixelAspect {
+    fn default() -> Self {
+        Self::SQUARE
+    }
+}
+
+// How a grid is laid over an image, resolved against the size of each image at
+// conversion time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridSpec {
+    // Divisions across and down
+    Cells { columns: u32, rows: u32 },
+    // Cell size in source pixels
+    CellSize { width: u32, height: u32 },
+    // `columns` divisions across; rows follow the image's aspect ratio
+    TargetWidth { columns: u32 },
+    // Square cells, about `DEFAULT_GRID_CELLS` of them along the longer side. A fixed
+    // starting point; it does not look at the image content.
+    DefaultCells,
+}
+
+impl GridSpec {
+    // Checks what can be known without an image
+    pub fn validate(&self) -> PixelArtResult<()> {
+        match *self {
+            GridSpec::Cells { columns, rows } if columns == 0 || rows == 0 => {
+                Err(PixelArtError::InvalidGrid { columns, rows })
+            }
+            GridSpec::CellSize { width, height } if width == 0 || height == 0 => {
+                Err(PixelArtError::InvalidCellSize { width, height })
+            }
+            GridSpec::TargetWidth { columns: 0 } => Err(PixelArtError::InvalidGrid {
+                columns: 0,
+                rows: 0,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // The grid for a `width` x `height` image; every cell must lie inside the image
+    pub fn resolve(&self, width: u32, height: u32) -> PixelArtResult<Grid> {
+        self.resolve_with_aspect(width, height, PixelAspect::SQUARE)
+    }
+
+    // Like `resolve`, but where the spec leaves the cell shape open (`TargetWidth` and
+    // `DefaultCells`), cells are `aspect` times as wide as tall, so the art keeps the image's
+    // proportions on a display with that pixel aspect. Explicit cell counts and sizes
+    // are used as given.
+    pub fn resolve_with_aspect(
+        &self,
+        width: u32,
+        height: u32,
+        aspect: PixelAspect,
+    ) -> PixelArtResult<Grid> {
+        self.validate()?;
+        if aspect.width == 0 || aspect.height == 0 {
+            return Err(PixelArtError::InvalidConfig(format!(
+                "pixel aspect must be positive, got {}:{}",
+                aspect.width, aspect.height
+            )));
+        }
+        let grid = match *self {
+            GridSpec::Cells { columns, rows } => Grid::new(width, height, columns, rows),
+            GridSpec::CellSize {
+                width: cell_width,
+                height: cell_height,
+            } => {
+                if cell_width > width || cell_height > height {
+                    return Err(PixelArtError::CellLargerThanImage {
+                        cell_width,
+                        cell_height,
+                        width,
+                        height,
+                    });
+                }
+                Grid::with_cell_size(width, height, cell_width, cell_height)
+            }
+            GridSpec::TargetWidth { columns } => {
+                // Rows of cells 1 / aspect as tall as they are wide
+                let numerator = columns as u64 * height as u64 * aspect.width as u64;
+                let denominator = width as u64 * aspect.height as u64;
+                let rows = (numerator + denominator / 2)
+                    .checked_div(denominator)
+                    .unwrap_or(0)
+                    .max(1);
+                Grid::new(width, height, columns, rows.min(u32::MAX as u64) as u32)
+            }
+            GridSpec::DefaultCells => {
+                let scaled_width = (width as f64 / aspect.ratio()) as u32;
+                let cell = (scaled_width.max(height) / DEFAULT_GRID_CELLS).max(1);
+                // A wide aspect on a thin image can make cells wider than the image
+                let cell_width = aspect.scaled_width(cell).min(width);
+                Grid::with_cell_size(width, height, cell_width, cell.min(height))
+            }
+        };
+
+        let (columns, rows) = (grid.width(), grid.height());
+        if columns > width || rows > height {
+            return Err(PixelArtError::GridLargerThanImage {
+                columns,
+                rows,
+                width,
+                height,
+            });
+        }
+        Ok(grid)
+    }
+}
+
+// A grid's cell counts, re-fitted to each image
+impl From<Grid> for GridSpec {
+    fn from(grid: Grid) -> Self {
+        GridSpec::Cells {
+            columns: grid.width(),
+            rows: grid.height(),
+        }
+    }
+}
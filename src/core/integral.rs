@@ -0,0 +1,254 @@
+use crate::core::color::{lab_values_to_rgba, rgba_to_lab_values};
+use image::{Rgba, RgbaImage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegralSpace {
+    Rgb,
+    Lab,
+}
+
+// Largest region whose 8-bit squares are sure to sum below 2^32
+const BLOCK_PIXELS: u32 = u32::MAX / (255 * 255);
+
+// Summed-area table giving the sum and sum of squares of every channel over any
+// rectangle in constant time.
+//
+// It holds (width + 1) x (height + 1) entries: 24 bytes each for 8-bit RGB and 48 for
+// Lab or `from_fn` values, so a 7680x4320 image takes about 0.8 GB in RGB and 1.6 GB
+// in Lab on top of the image itself. See `table_bytes`.
+#[derive(Debug, Clone)]
+pub struct IntegralImage {
+    width: u32,
+    height: u32,
+    space: IntegralSpace,
+    tables: Tables,
+}
+
+#[derive(Debug, Clone)]
+enum Tables {
+    // Wrapping 32-bit sums. A rectangle comes out exact whenever its true sum fits in
+    // 32 bits, so regions are read in blocks of at most `BLOCK_PIXELS`.
+    Rgb {
+        sums: Vec<[u32; 3]>,
+        squares: Vec<[u32; 3]>,
+    },
+    Float {
+        sums: Vec<[f64; 3]>,
+        squares: Vec<[f64; 3]>,
+    },
+}
+
+impl IntegralImage {
+    pub fn new(image: &RgbaImage, space: IntegralSpace) -> Self {
+        let (width, height) = image.dimensions();
+        match space {
+            IntegralSpace::Rgb => {
+                let (sums, squares) = build_tables(width, height, |x, y| {
+                    let [r, g, b, _] = image.get_pixel(x, y).0;
+                    [r as u32, g as u32, b as u32]
+                });
+                Self {
+                    width,
+                    height,
+                    space,
+                    tables: Tables::Rgb { sums, squares },
+                }
+            }
+            IntegralSpace::Lab => Self::from_fn(width, height, space, |x, y| {
+                rgba_to_lab_values(image.get_pixel(x, y)).map(|v| v as f64)
+            }),
+        }
+    }
+
+    // Table over arbitrary per-pixel values, e.g. edge magnitudes; `space` only
+    // controls how `region_stats` turns the mean back into a color
+    pub fn from_fn<F>(width: u32, height: u32, space: IntegralSpace, values: F) -> Self
+    where
+        F: Fn(u32, u32) -> [f64; 3],
+    {
+        let (sums, squares) = build_tables(width, height, values);
+        Self {
+            width,
+            height,
+            space,
+            tables: Tables::Float { sums, squares },
+        }
+    }
+
+    // Bytes `new` allocates for a `width` x `height` image
+    pub fn table_bytes(width: u32, height: u32, space: IntegralSpace) -> u64 {
+        let entries = (width as u64 + 1) * (height as u64 + 1);
+        let entry = match space {
+            IntegralSpace::Rgb => 2 * size_of::<[u32; 3]>(),
+            IntegralSpace::Lab => 2 * size_of::<[f64; 3]>(),
+        };
+        entries * entry as u64
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn space(&self) -> IntegralSpace {
+        self.space
+    }
+
+    pub fn region_sum(&self, x: u32, y: u32, w: u32, h: u32) -> [f64; 3] {
+        match &self.tables {
+            Tables::Rgb { sums, .. } => self.block_sum(sums, x, y, w, h),
+            Tables::Float { sums, .. } => rect(sums, self.stride(), x, y, w, h),
+        }
+    }
+
+    pub fn region_square_sum(&self, x: u32, y: u32, w: u32, h: u32) -> [f64; 3] {
+        match &self.tables {
+            Tables::Rgb { squares, .. } => self.block_sum(squares, x, y, w, h),
+            Tables::Float { squares, .. } => rect(squares, self.stride(), x, y, w, h),
+        }
+    }
+
+    fn stride(&self) -> usize {
+        self.width as usize + 1
+    }
+
+    // Sum over a wrapping table, added up in 64 bits from blocks small enough to be exact
+    fn block_sum(&self, table: &[[u32; 3]], x: u32, y: u32, w: u32, h: u32) -> [f64; 3] {
+        let block_width = w.clamp(1, BLOCK_PIXELS);
+        let block_height = (BLOCK_PIXELS / block_width).max(1);
+        let mut total = [0u64; 3];
+        for by in (y..y + h).step_by(block_height as usize) {
+            for bx in (x..x + w).step_by(block_width as usize) {
+                let (bw, bh) = (block_width.min(x + w - bx), block_height.min(y + h - by));
+                let block = rect(table, self.stride(), bx, by, bw, bh);
+                for c in 0..3 {
+                    total[c] += block[c] as u64;
+                }
+            }
+        }
+        total.map(|sum| sum as f64)
+    }
+
+    pub fn region_mean(&self, x: u32, y: u32, w: u32, h: u32) -> [f64; 3] {
+        let count = (w as f64 * h as f64).max(1.0);
+        self.region_sum(x, y, w, h).map(|sum| sum / count)
+    }
+
+    // Per-channel population variance
+    pub fn region_channel_variance(&self, x: u32, y: u32, w: u32, h: u32) -> [f64; 3] {
+        let count = (w as f64 * h as f64).max(1.0);
+        let sum = self.region_sum(x, y, w, h);
+        let square = self.region_square_sum(x, y, w, h);
+        [0, 1, 2].map(|c| {
+            let mean = sum[c] / count;
+            (square[c] / count - mean * mean).max(0.0)
+        })
+    }
+
+    // Mean color and total variance (mean squared distance from the mean), matching
+    // `calculate_color_variance` when the table is built in Lab
+    pub fn region_stats(&self, x: u32, y: u32, w: u32, h: u32) -> (Rgba<u8>, f64) {
+        if w == 0 || h == 0 {
+            return (Rgba([0, 0, 0, 255]), 0.0);
+        }
+
+        let mean = self.region_mean(x, y, w, h);
+        let variance = self.region_channel_variance(x, y, w, h).iter().sum();
+        let color = match self.space {
+            IntegralSpace::Rgb => Rgba([mean[0] as u8, mean[1] as u8, mean[2] as u8, 255]),
+            IntegralSpace::Lab => {
+                lab_values_to_rgba([mean[0] as f32, mean[1] as f32, mean[2] as f32])
+            }
+        };
+        (color, variance)
+    }
+}
+
+trait TableValue: Copy {
+    const ZERO: Self;
+    fn add(self, other: Self) -> Self;
+    fn sub(self, other: Self) -> Self;
+    fn mul(self, other: Self) -> Self;
+}
+
+impl TableValue for u32 {
+    const ZERO: Self = 0;
+
+    fn add(self, other: Self) -> Self {
+        self.wrapping_add(other)
+    }
+
+    fn sub(self, other: Self) -> Self {
+        self.wrapping_sub(other)
+    }
+
+    fn mul(self, other: Self) -> Self {
+        self.wrapping_mul(other)
+    }
+}
+
+impl TableValue for f64 {
+    const ZERO: Self = 0.0;
+
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn sub(self, other: Self) -> Self {
+        self - other
+    }
+
+    fn mul(self, other: Self) -> Self {
+        self * other
+    }
+}
+
+// Sums and squares tables with a zero first row and column
+type TablePair<T> = (Vec<[T; 3]>, Vec<[T; 3]>);
+
+fn build_tables<T, F>(width: u32, height: u32, values: F) -> TablePair<T>
+where
+    T: TableValue,
+    F: Fn(u32, u32) -> [T; 3],
+{
+    let stride = width as usize + 1;
+
+    let mut sums = vec![[T::ZERO; 3]; stride * (height as usize + 1)];
+    let mut squares = vec![[T::ZERO; 3]; stride * (height as usize + 1)];
+
+    for y in 0..height as usize {
+        let mut row_sum = [T::ZERO; 3];
+        let mut row_square = [T::ZERO; 3];
+
+        for x in 0..width as usize {
+            let values = values(x as u32, y as u32);
+            for c in 0..3 {
+                row_sum[c] = row_sum[c].add(values[c]);
+                row_square[c] = row_square[c].add(values[c].mul(values[c]));
+            }
+
+            let above = y * stride + x + 1;
+            let here = above + stride;
+            for c in 0..3 {
+                sums[here][c] = sums[above][c].add(row_sum[c]);
+                squares[here][c] = squares[above][c].add(row_square[c]);
+            }
+        }
+    }
+
+    (sums, squares)
+}
+
+fn rect<T: TableValue>(table: &[[T; 3]], stride: usize, x: u32, y: u32, w: u32, h: u32) -> [T; 3] {
+    let (x0, y0) = (x as usize, y as usize);
+    let (x1, y1) = (x0 + w as usize, y0 + h as usize);
+
+    let a = table[y0 * stride + x0];
+    let b = table[y0 * stride + x1];
+    let c = table[y1 * stride + x0];
+    let d = table[y1 * stride + x1];
+    [0, 1, 2].map(|i| d[i].sub(b[i]).sub(c[i]).add(a[i]))
+}
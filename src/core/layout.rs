@@ -0,0 +1,182 @@
+use crate::core::grid::Grid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexOrientation {
+    // Vertex at the top; rows are staggered by half a cell
+    Pointy,
+    // Flat top edge; columns are staggered by half a cell
+    Flat,
+}
+
+// How cells tile the grid area
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GridLayout {
+    #[default]
+    Rectangular,
+    Hexagonal(HexOrientation),
+    // Isometric diamonds as wide and tall as a cell, on half-cell row steps
+    Diamond,
+    // Rectangles with every other row shifted by half a cell
+    Brick,
+}
+
+// A layout laid over a resolved grid. Cells keep the grid's cell size and cover the
+// same `columns * cell_width` x `rows * cell_height` area; staggered layouts have
+// clipped cells along the edges. Cells are addressed by (row, col) in a row-major
+// index space of `index_rows` x `index_columns`, where some slots may not be cells.
+#[derive(Debug, Clone)]
+pub struct TiledGrid {
+    layout: GridLayout,
+    grid: Grid,
+}
+
+impl TiledGrid {
+    pub fn new(layout: GridLayout, grid: Grid) -> Self {
+        Self { layout, grid }
+    }
+
+    pub fn layout(&self) -> GridLayout {
+        self.layout
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    // Size of the area the cells cover
+    pub fn extent(&self) -> (u32, u32) {
+        (
+            self.grid.width() * self.grid.cell_width(),
+            self.grid.height() * self.grid.cell_height(),
+        )
+    }
+
+    pub fn index_rows(&self) -> u32 {
+        let rows = self.grid.height();
+        match self.layout {
+            GridLayout::Rectangular | GridLayout::Brick => rows,
+            GridLayout::Hexagonal(HexOrientation::Pointy) => rows,
+            GridLayout::Hexagonal(HexOrientation::Flat) => rows + 1,
+            GridLayout::Diamond => 2 * rows + 1,
+        }
+    }
+
+    pub fn index_columns(&self) -> u32 {
+        let columns = self.grid.width();
+        match self.layout {
+            GridLayout::Rectangular | GridLayout::Hexagonal(HexOrientation::Flat) => columns,
+            GridLayout::Brick
+            | GridLayout::Hexagonal(HexOrientation::Pointy)
+            | GridLayout::Diamond => columns + 1,
+        }
+    }
+
+    // Staggered rows or columns hold one cell more than the others
+    pub fn is_cell(&self, row: u32, col: u32) -> bool {
+        let (rows, columns) = (self.grid.height(), self.grid.width());
+        let odd = |n: u32| n % 2 == 1;
+        match self.layout {
+            GridLayout::Rectangular => row < rows && col < columns,
+            GridLayout::Brick | GridLayout::Hexagonal(HexOrientation::Pointy) => {
+                row < rows && (col < columns || (odd(row) && col == columns))
+            }
+            GridLayout::Hexagonal(HexOrientation::Flat) => {
+                col < columns && (row < rows || (odd(col) && row == rows))
+            }
+            GridLayout::Diamond => {
+                row <= 2 * rows && (col < columns || (!odd(row) && col == columns))
+            }
+        }
+    }
+
+    pub fn iter_cells(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let columns = self.index_columns();
+        (0..self.index_rows())
+            .flat_map(move |row| (0..columns).map(move |col| (row, col)))
+            .filter(|&(row, col)| self.is_cell(row, col))
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.iter_cells().count()
+    }
+
+    // Center of a cell in pixel coordinates; may lie on the edge of the covered area
+    pub fn cell_center(&self, row: u32, col: u32) -> (f64, f64) {
+        let (w, h) = self.cell_size();
+        let (row, col) = (row as f64, col as f64);
+        let odd = |n: f64| n as u32 % 2 == 1;
+        match self.layout {
+            GridLayout::Rectangular => ((col + 0.5) * w, (row + 0.5) * h),
+            GridLayout::Brick | GridLayout::Hexagonal(HexOrientation::Pointy) => {
+                let shift = if odd(row) { 0.0 } else { 0.5 };
+                ((col + shift) * w, (row + 0.5) * h)
+            }
+            GridLayout::Hexagonal(HexOrientation::Flat) => {
+                let shift = if odd(col) { 0.0 } else { 0.5 };
+                ((col + 0.5) * w, (row + shift) * h)
+            }
+            GridLayout::Diamond => {
+                let shift = if odd(row) { 0.5 } else { 0.0 };
+                ((col + shift) * w, row * h / 2.0)
+            }
+        }
+    }
+
+    // The cell pixel (x, y) belongs to, or None outside the covered area
+    pub fn cell_at(&self, x: u32, y: u32) -> Option<(u32, u32)> {
+        let (width, height) = self.extent();
+        if x >= width || y >= height {
+            return None;
+        }
+        if self.layout == GridLayout::Rectangular {
+            return Some((y / self.grid.cell_height(), x / self.grid.cell_width()));
+        }
+
+        // The nearest center among the cells around the pixel's approximate index
+        let (w, h) = self.cell_size();
+        let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+        let (row, col) = match self.layout {
+            GridLayout::Diamond => ((py / (h / 2.0)).round(), (px / w).floor()),
+            _ => ((py / h).floor(), (px / w).floor()),
+        };
+
+        let mut best = None;
+        let mut best_distance = f64::INFINITY;
+        for row in (row as i64 - 1)..=(row as i64 + 1) {
+            for col in (col as i64 - 1)..=(col as i64 + 1) {
+                if row < 0 || col < 0 || !self.is_cell(row as u32, col as u32) {
+                    continue;
+                }
+                let (cx, cy) = self.cell_center(row as u32, col as u32);
+                let distance = self.distance(px - cx, py - cy);
+                if distance < best_distance {
+                    best_distance = distance;
+                    best = Some((row as u32, col as u32));
+                }
+            }
+        }
+        best
+    }
+
+    // Position of a cell in a row-major buffer of `index_rows * index_columns` entries
+    pub fn cell_index(&self, row: u32, col: u32) -> usize {
+        row as usize * self.index_columns() as usize + col as usize
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        (
+            self.grid.cell_width() as f64,
+            self.grid.cell_height() as f64,
+        )
+    }
+
+    // The metric whose nearest-center regions have the layout's cell shape
+    fn distance(&self, dx: f64, dy: f64) -> f64 {
+        let (w, h) = self.cell_size();
+        match self.layout {
+            GridLayout::Rectangular | GridLayout::Brick => (dx / w).abs().max((dy / h).abs()),
+            GridLayout::Hexagonal(_) => dx.hypot(dy),
+            GridLayout::Diamond => (dx / w).abs() + (dy / h).abs(),
+        }
+    }
+}
@@ -1,11 +1,31 @@
 pub mod color;
+pub mod detect;
+pub mod dither;
+pub mod error;
 pub mod grid;
+pub mod integral;
+pub mod layout;
+pub mod palette;
 pub mod pixel_art;
+pub mod progress;
 pub mod quadtree;
 pub mod quantization;
+pub mod split;
+pub mod stream;
+pub mod view;
 
 pub use color::*;
+pub use detect::*;
+pub use dither::*;
+pub use error::*;
 pub use grid::*;
+pub use integral::*;
+pub use layout::*;
+pub use palette::*;
 pub use pixel_art::*;
+pub use progress::*;
 pub use quadtree::*;
 pub use quantization::*;
+pub use split::*;
+pub use stream::*;
+pub use view::*;
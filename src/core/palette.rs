@@ -0,0 +1,359 @@
+use crate::core::color::{color_distance_lab, rgba_to_lab};
+use crate::core::error::{PixelArtError, PixelArtResult};
+use image::{Rgba, RgbaImage};
+use palette::{FromColor, Hsv, Srgb};
+use std::collections::HashMap;
+use std::io::Write;
+
+// Colors below this Lab chroma are treated as greys without a meaningful hue
+pub const NEUTRAL_CHROMA: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteOrder {
+    Hue,
+    Luminance,
+    SimilarityChain,
+    Ramps,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lch {
+    pub l: f32,
+    pub c: f32,
+    pub h: f32,
+}
+
+pub fn rgba_to_lch(color: &Rgba<u8>) -> Lch {
+    let lab = rgba_to_lab(color);
+    Lch {
+        l: lab.l,
+        c: (lab.a * lab.a + lab.b * lab.b).sqrt(),
+        h: lab.b.atan2(lab.a).to_degrees().rem_euclid(360.0),
+    }
+}
+
+pub fn sort_palette(palette: &mut Vec<Rgba<u8>>, order: PaletteOrder) {
+    match order {
+        PaletteOrder::Hue => palette.sort_by(|a, b| {
+            let (a, b) = (hue_key(a), hue_key(b));
+            a.0.cmp(&b.0)
+                .then(a.1.total_cmp(&b.1))
+                .then(a.2.total_cmp(&b.2))
+        }),
+        PaletteOrder::Luminance => {
+            palette.sort_by(|a, b| rgba_to_lch(a).l.total_cmp(&rgba_to_lch(b).l))
+        }
+        PaletteOrder::SimilarityChain => *palette = similarity_chain(palette),
+        PaletteOrder::Ramps => *palette = group_into_ramps(palette, 30.0).concat(),
+    }
+}
+
+// Neutrals first, then by hue, then dark to light
+fn hue_key(color: &Rgba<u8>) -> (bool, f32, f32) {
+    let lch = rgba_to_lch(color);
+    if lch.c < NEUTRAL_CHROMA {
+        (false, 0.0, lch.l)
+    } else {
+        (true, lch.h, lch.l)
+    }
+}
+
+// Greedy walk from the darkest color, always stepping to the closest unvisited one
+fn similarity_chain(palette: &[Rgba<u8>]) -> Vec<Rgba<u8>> {
+    let mut remaining = palette.to_vec();
+    let mut chain = Vec::with_capacity(remaining.len());
+
+    let start = remaining
+        .iter()
+        .enumerate()
+        .min_by(|a, b| rgba_to_lch(a.1).l.total_cmp(&rgba_to_lch(b.1).l))
+        .map(|(i, _)| i);
+    let Some(start) = start else {
+        return chain;
+    };
+    chain.push(remaining.swap_remove(start));
+
+    while !remaining.is_empty() {
+        let last = chain[chain.len() - 1];
+        let next = remaining
+            .iter()
+            .enumerate()
+            .min_by(|a, b| {
+                color_distance_lab(&last, a.1).total_cmp(&color_distance_lab(&last, b.1))
+            })
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        chain.push(remaining.swap_remove(next));
+    }
+
+    chain
+}
+
+// Groups colors into shading ramps of similar hue, each ordered dark to light.
+// Neutral colors form their own ramp which comes first.
+pub fn group_into_ramps(palette: &[Rgba<u8>], hue_tolerance: f32) -> Vec<Vec<Rgba<u8>>> {
+    let mut neutrals = Vec::new();
+    let mut chromatic: Vec<(f32, Rgba<u8>)> = Vec::new();
+    for color in palette {
+        let lch = rgba_to_lch(color);
+        if lch.c < NEUTRAL_CHROMA {
+            neutrals.push(*color);
+        } else {
+            chromatic.push((lch.h, *color));
+        }
+    }
+    chromatic.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut ramps: Vec<Vec<(f32, Rgba<u8>)>> = Vec::new();
+    if !chromatic.is_empty() {
+        // Start the sweep after the widest hue gap so no ramp straddles the wrap-around
+        let n = chromatic.len();
+        let start = (0..n)
+            .max_by(|&a, &b| {
+                hue_gap(chromatic[(a + n - 1) % n].0, chromatic[a].0)
+                    .total_cmp(&hue_gap(chromatic[(b + n - 1) % n].0, chromatic[b].0))
+            })
+            .unwrap_or(0);
+
+        for offset in 0..n {
+            let entry = chromatic[(start + offset) % n];
+            match ramps.last_mut() {
+                Some(ramp) if hue_gap(ramp[ramp.len() - 1].0, entry.0) <= hue_tolerance => {
+                    ramp.push(entry)
+                }
+                _ => ramps.push(vec![entry]),
+            }
+        }
+    }
+
+    let mut result = Vec::with_capacity(ramps.len() + 1);
+    if !neutrals.is_empty() {
+        result.push(neutrals);
+    }
+    result.extend(
+        ramps
+            .into_iter()
+            .map(|ramp| ramp.into_iter().map(|(_, color)| color).collect()),
+    );
+    for ramp in &mut result {
+        ramp.sort_by(|a: &Rgba<u8>, b: &Rgba<u8>| rgba_to_lch(a).l.total_cmp(&rgba_to_lch(b).l));
+    }
+    result
+}
+
+// Hue distance travelled going forward from `from` to `to`
+fn hue_gap(from: f32, to: f32) -> f32 {
+    (to - from).rem_euclid(360.0)
+}
+
+// Index pairs of colors closer than `threshold` in Lab space
+pub fn find_near_duplicates(palette: &[Rgba<u8>], threshold: f64) -> Vec<(usize, usize, f64)> {
+    let mut pairs = Vec::new();
+    for i in 0..palette.len() {
+        for j in (i + 1)..palette.len() {
+            let distance = color_distance_lab(&palette[i], &palette[j]);
+            if distance < threshold {
+                pairs.push((i, j, distance));
+            }
+        }
+    }
+    pairs
+}
+
+// Drops later colors that duplicate an earlier kept color, so earlier entries win
+pub fn merge_near_duplicates(palette: &[Rgba<u8>], threshold: f64) -> Vec<Rgba<u8>> {
+    let mut kept: Vec<Rgba<u8>> = Vec::with_capacity(palette.len());
+    for color in palette {
+        if kept
+            .iter()
+            .all(|existing| color_distance_lab(existing, color) >= threshold)
+        {
+            kept.push(*color);
+        }
+    }
+    kept
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SaturationCurve {
+    Constant,
+    // Saturation peaks at the base color and drops by `falloff` at both ends
+    Arc { falloff: f32 },
+    // Multipliers applied at the darkest and lightest step, interpolated through the base
+    Linear { shadow: f32, highlight: f32 },
+}
+
+impl SaturationCurve {
+    fn factor(&self, t: f32) -> f32 {
+        match *self {
+            SaturationCurve::Constant => 1.0,
+            SaturationCurve::Arc { falloff } => 1.0 - falloff * t * t,
+            SaturationCurve::Linear { shadow, highlight } => {
+                if t < 0.0 {
+                    1.0 + (shadow - 1.0) * -t
+                } else {
+                    1.0 + (highlight - 1.0) * t
+                }
+            }
+        }
+    }
+}
+
+// Builds hand-shaded style ramps: shadows drift toward a cool hue, highlights toward a warm one
+#[derive(Debug, Clone)]
+pub struct RampGenerator {
+    pub steps: u32,
+    // Maximum hue rotation in degrees reached at either end of the ramp
+    pub hue_shift: f32,
+    // Fraction of the available value range used toward black and white (0.0..=1.0)
+    pub value_spread: f32,
+    pub saturation_curve: SaturationCurve,
+    pub warm_hue: f32,
+    pub cool_hue: f32,
+}
+
+impl RampGenerator {
+    pub fn new(steps: u32) -> Self {
+        Self {
+            steps,
+            hue_shift: 20.0,
+            value_spread: 0.8,
+            saturation_curve: SaturationCurve::Linear {
+                shadow: 1.2,
+                highlight: 0.7,
+            },
+            warm_hue: 60.0,
+            cool_hue: 240.0,
+        }
+    }
+
+    pub fn with_hue_shift(mut self, hue_shift: f32) -> Self {
+        self.hue_shift = hue_shift;
+        self
+    }
+
+    pub fn with_value_spread(mut self, value_spread: f32) -> Self {
+        self.value_spread = value_spread.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_saturation_curve(mut self, curve: SaturationCurve) -> Self {
+        self.saturation_curve = curve;
+        self
+    }
+
+    // Dark to light; an odd step count keeps the base color as the middle step
+    pub fn ramp(&self, base: &Rgba<u8>) -> Vec<Rgba<u8>> {
+        if self.steps == 0 {
+            return vec![];
+        }
+        if self.steps == 1 {
+            return vec![Rgba([base.0[0], base.0[1], base.0[2], 255])];
+        }
+
+        let hsv: Hsv = Hsv::from_color(Srgb::new(
+            base.0[0] as f32 / 255.0,
+            base.0[1] as f32 / 255.0,
+            base.0[2] as f32 / 255.0,
+        ));
+        let base_hue = hsv.hue.into_positive_degrees();
+
+        (0..self.steps)
+            .map(|i| {
+                let t = -1.0 + 2.0 * i as f32 / (self.steps - 1) as f32;
+
+                let (target_hue, value_room) = if t < 0.0 {
+                    (self.cool_hue, hsv.value)
+                } else {
+                    (self.warm_hue, 1.0 - hsv.value)
+                };
+                let hue = rotate_hue_toward(base_hue, target_hue, self.hue_shift * t.abs());
+                let value = (hsv.value + t * value_room * self.value_spread).clamp(0.0, 1.0);
+                let saturation = (hsv.saturation * self.saturation_curve.factor(t)).clamp(0.0, 1.0);
+
+                let rgb = Srgb::from_color(Hsv::new(hue, saturation, value));
+                Rgba([
+                    (rgb.red.clamp(0.0, 1.0) * 255.0).round() as u8,
+                    (rgb.green.clamp(0.0, 1.0) * 255.0).round() as u8,
+                    (rgb.blue.clamp(0.0, 1.0) * 255.0).round() as u8,
+                    255,
+                ])
+            })
+            .collect()
+    }
+
+    pub fn ramps(&self, bases: &[Rgba<u8>]) -> Vec<Vec<Rgba<u8>>> {
+        bases.iter().map(|base| self.ramp(base)).collect()
+    }
+
+    // All ramps concatenated, ready to use as a fixed palette
+    pub fn palette(&self, bases: &[Rgba<u8>]) -> Vec<Rgba<u8>> {
+        self.ramps(bases).concat()
+    }
+}
+
+// Rotates along the shorter arc toward `target`, never overshooting it
+fn rotate_hue_toward(hue: f32, target: f32, amount: f32) -> f32 {
+    let delta = (target - hue + 540.0).rem_euclid(360.0) - 180.0;
+    let step = delta.signum() * amount.min(delta.abs());
+    (hue + step).rem_euclid(360.0)
+}
+
+// GIMP palette file, one entry per color in palette order
+pub fn palette_to_gpl(palette: &[Rgba<u8>], name: &str) -> String {
+    let mut text = format!("GIMP Palette\nName: {name}\nColumns: 8\n#\n");
+    for color in palette {
+        let [r, g, b, _] = color.0;
+        text.push_str(&format!("{r:3} {g:3} {b:3}\t#{r:02x}{g:02x}{b:02x}\n"));
+    }
+    text
+}
+
+// One `rrggbb` line per color, as used by Lospec and most editors
+pub fn palette_to_hex(palette: &[Rgba<u8>]) -> String {
+    palette
+        .iter()
+        .map(|color| format!("{:02x}{:02x}{:02x}\n", color.0[0], color.0[1], color.0[2]))
+        .collect()
+}
+
+// Writes `image` as an 8-bit indexed PNG whose first entries are `palette` in order, so
+// pixel indices follow the palette. Colors missing from it, such as transparent padding,
+// get entries after the palette.
+pub fn write_indexed_png<W: Write>(
+    output: W,
+    image: &RgbaImage,
+    palette: &[Rgba<u8>],
+) -> PixelArtResult<()> {
+    let mut entries: Vec<Rgba<u8>> = Vec::with_capacity(palette.len());
+    let mut index_of: HashMap<Rgba<u8>, usize> = HashMap::new();
+    for color in palette.iter().chain(image.pixels()) {
+        index_of.entry(*color).or_insert_with(|| {
+            entries.push(*color);
+            entries.len() - 1
+        });
+    }
+    if entries.len() > 256 {
+        return Err(PixelArtError::TooManyColors {
+            colors: entries.len(),
+        });
+    }
+    let indices: Vec<u8> = image.pixels().map(|pixel| index_of[pixel] as u8).collect();
+
+    let mut encoder = png::Encoder::new(output, image.width(), image.height());
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(
+        entries
+            .iter()
+            .flat_map(|color| [color.0[0], color.0[1], color.0[2]])
+            .collect::<Vec<u8>>(),
+    );
+    if entries.iter().any(|color| color.0[3] < 255) {
+        encoder.set_trns(entries.iter().map(|color| color.0[3]).collect::<Vec<u8>>());
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&indices)?;
+    writer.finish()?;
+    Ok(())
+}
@@ -1,16 +1,23 @@
-use crate::core::color::ColorExtractor;
-use crate::core::grid::Grid;
-use crate::core::quadtree::QuadTree;
-use anyhow::Result;
+use crate::core::color::{AverageColorExtractor, ColorExtractor};
+use crate::core::dither::{Dithering, dither_to_palette};
+use crate::core::error::{PixelArtError, PixelArtResult};
+use crate::core::grid::{Grid, GridSpec, PixelAspect, Rect, grid_region};
+use crate::core::integral::{IntegralImage, IntegralSpace};
+use crate::core::layout::{GridLayout, TiledGrid};
+use crate::core::progress::ProgressFn;
+use crate::core::quadtree::{DEFAULT_PARALLEL_CUTOFF, QuadNode, QuadTree, QuadTreeConfig};
+use crate::core::quantization::PaletteQuantizer;
+use crate::core::split::{SplitContext, UniformityCriterion};
+use crate::core::stream::{RowSink, RowSource};
+use crate::core::view::PixelView;
 use image::{DynamicImage, Rgba, RgbaImage};
 use rayon::prelude::*;
-use std::cell::RefCell;
+use std::borrow::Cow;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU32, Ordering};
 
-thread_local! {
-    static PIXEL_BUFFER: RefCell<Vec<Rgba<u8>>> = RefCell::new(Vec::with_capacity(65536));
-}
+// Receives the palette a conversion mapped its colors to
+pub type PaletteFn = Arc<dyn Fn(&[Rgba<u8>]) + Send + Sync>;
 
 #[allow(dead_code)]
 struct PixelDataSoA {
@@ -62,110 +69,517 @@ impl PixelDataSoA {
     }
 }
 
+// Borrows RGBA8 input as is; other formats are converted once and shared by every cell
+fn rgba_pixels(image: &DynamicImage) -> Cow<'_, RgbaImage> {
+    match image {
+        DynamicImage::ImageRgba8(rgba) => Cow::Borrowed(rgba),
+        other => Cow::Owned(other.to_rgba8()),
+    }
+}
+
+// The pixels of `area`, borrowed when it covers the whole image
+fn area_pixels(image: &DynamicImage, area: Rect) -> Cow<'_, RgbaImage> {
+    if (area.x, area.y, area.width, area.height) == (0, 0, image.width(), image.height()) {
+        return rgba_pixels(image);
+    }
+    let rgba = rgba_pixels(image);
+    Cow::Owned(
+        image::imageops::crop_imm(&*rgba, area.x, area.y, area.width, area.height).to_image(),
+    )
+}
+
+// Paints every cell's color over its bounds, clipped to the image; pixels outside the
+// grid stay transparent black. Each cell row is one band of output rows, so bands are
+// independent and can be written in parallel.
+fn fill_grid(image: &mut RgbaImage, grid: &Grid, colors: &[Rgba<u8>], parallel: bool) {
+    let row_bytes = image.width() as usize * 4;
+    let (cell_width, band_rows) = (grid.cell_width() as usize, grid.cell_height() as usize);
+    if row_bytes == 0 || cell_width == 0 || band_rows == 0 {
+        return;
+    }
+
+    let columns = grid.width() as usize;
+    let fill_band = |(row, band): (usize, &mut [u8])| {
+        if row >= grid.height() as usize {
+            return;
+        }
+
+        let (first, rest) = band.split_at_mut(row_bytes);
+        fill_cell_row(
+            first,
+            &colors[row * columns..(row + 1) * columns],
+            cell_width,
+        );
+
+        // Every row of a band is identical to its first
+        for other in rest.chunks_exact_mut(row_bytes) {
+            other.copy_from_slice(first);
+        }
+    };
+
+    let band_bytes = row_bytes * band_rows;
+    if parallel {
+        image
+            .par_chunks_mut(band_bytes)
+            .enumerate()
+            .for_each(fill_band);
+    } else {
+        image.chunks_mut(band_bytes).enumerate().for_each(fill_band);
+    }
+}
+
+// Paints one output row crossing a row of cells, clipped to the row's width
+fn fill_cell_row(row: &mut [u8], row_colors: &[Rgba<u8>], cell_width: usize) {
+    let width = row.len() / 4;
+    for (col, color) in row_colors.iter().enumerate() {
+        let start = col * cell_width;
+        if start >= width {
+            break;
+        }
+        let end = (start + cell_width).min(width);
+        for pixel in row[start * 4..end * 4].chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color.0);
+        }
+    }
+}
+
+#[derive(Clone)]
 pub enum ProcessingStrategy {
-    UniformGrid(Grid),
-    AdaptiveQuadTree(QuadTree),
+    UniformGrid(GridSpec),
+    // The tree is built per image during conversion
+    AdaptiveQuadTree(QuadTreeConfig),
 }
 
 pub struct PixelArtConverter {
     strategy: ProcessingStrategy,
     color_extractor: Box<dyn ColorExtractor>,
     progress_callback: Option<Arc<dyn Fn(u32, u32) + Send + Sync>>,
+    tree_progress_callback: Option<ProgressFn>,
+    palette_callback: Option<PaletteFn>,
+    palette_quantizer: Option<PaletteQuantizer>,
+    merge_uniform: bool,
+    dithering: Dithering,
+    scale: Option<u32>,
+    crop: Option<Rect>,
+    offset: (u32, u32),
+    layout: GridLayout,
+    pixel_aspect: PixelAspect,
 }
 
 impl PixelArtConverter {
-    pub fn with_grid(grid: Grid, extractor: Box<dyn ColorExtractor>) -> Self {
+    fn new(strategy: ProcessingStrategy, extractor: Box<dyn ColorExtractor>) -> Self {
         Self {
-            strategy: ProcessingStrategy::UniformGrid(grid),
+            strategy,
             color_extractor: extractor,
             progress_callback: None,
+            tree_progress_callback: None,
+            palette_callback: None,
+            palette_quantizer: None,
+            merge_uniform: false,
+            dithering: Dithering::None,
+            scale: None,
+            crop: None,
+            offset: (0, 0),
+            layout: GridLayout::Rectangular,
+            pixel_aspect: PixelAspect::SQUARE,
         }
     }
 
+    pub fn builder() -> PixelArtConverterBuilder {
+        PixelArtConverterBuilder::default()
+    }
+
+    // Only the grid's cell counts are kept; cells are re-fitted to each image converted
+    pub fn with_grid(grid: Grid, extractor: Box<dyn ColorExtractor>) -> Self {
+        Self::new(ProcessingStrategy::UniformGrid(grid.into()), extractor)
+    }
+
     pub fn with_quadtree(
         max_depth: u32,
         variance_threshold: f64,
         extractor: Box<dyn ColorExtractor>,
     ) -> Self {
-        // Create a dummy image for now - in real implementation, this would be set during conversion
-        let dummy_image = RgbaImage::new(1, 1);
-        let quadtree = QuadTree::build(&dummy_image, max_depth, variance_threshold);
+        Self::with_quadtree_config(
+            QuadTreeConfig::new(max_depth, variance_threshold),
+            extractor,
+        )
+    }
 
-        Self {
-            strategy: ProcessingStrategy::AdaptiveQuadTree(quadtree),
-            color_extractor: extractor,
-            progress_callback: None,
-        }
+    pub fn with_quadtree_config(
+        config: QuadTreeConfig,
+        extractor: Box<dyn ColorExtractor>,
+    ) -> Self {
+        Self::new(ProcessingStrategy::AdaptiveQuadTree(config), extractor)
+    }
+
+    pub fn strategy(&self) -> &ProcessingStrategy {
+        &self.strategy
     }
 
+    // Grid mode: called with (row, col) as each cell finishes
     pub fn set_progress_callback(&mut self, callback: Arc<dyn Fn(u32, u32) + Send + Sync>) {
         self.progress_callback = Some(callback);
     }
 
-    pub fn convert(&self, image: &DynamicImage) -> Result<DynamicImage> {
-        match &self.strategy {
-            ProcessingStrategy::UniformGrid(grid) => self.process_with_grid(image, grid),
-            ProcessingStrategy::AdaptiveQuadTree(_) => self.process_with_quadtree(image),
+    // Quadtree mode: called with (pixels done, pixels total) as leaves are finalized
+    pub fn set_tree_progress_callback(&mut self, callback: ProgressFn) {
+        self.tree_progress_callback = Some(callback);
+    }
+
+    // Grid mode only: skip color extraction for runs of identical cells found by a
+    // quadtree over the cell lattice. The output is identical to the plain grid.
+    pub fn set_merge_uniform(&mut self, merge_uniform: bool) {
+        self.merge_uniform = merge_uniform;
+    }
+
+    pub fn set_palette_quantizer(&mut self, quantizer: PaletteQuantizer) {
+        self.palette_quantizer = Some(quantizer);
+    }
+
+    // Reduce extracted cell colors to a shared palette, keeping any locked colors.
+    // `colors` is row-major with `columns` cells per row, which dithering relies on.
+    fn apply_palette(&self, colors: &mut [Rgba<u8>], columns: usize) {
+        if let Some(quantizer) = &self.palette_quantizer {
+            let palette = quantizer.build_palette(colors);
+            self.report_palette(&palette);
+            dither_to_palette(colors, columns, &palette, self.dithering);
         }
     }
 
-    pub fn convert_parallel(&self, image: &DynamicImage) -> Result<DynamicImage> {
-        match &self.strategy {
-            ProcessingStrategy::UniformGrid(grid) => self.process_with_grid_parallel(image, grid),
-            ProcessingStrategy::AdaptiveQuadTree(_) => self.process_with_quadtree(image),
+    fn report_palette(&self, palette: &[Rgba<u8>]) {
+        if let Some(callback) = &self.palette_callback {
+            callback(palette);
         }
     }
 
-    fn process_with_grid(&self, image: &DynamicImage, grid: &Grid) -> Result<DynamicImage> {
-        let width = image.width();
-        let height = image.height();
-        let mut result_data = vec![0u8; (width * height * 4) as usize];
+    fn check_size(width: u32, height: u32) -> PixelArtResult<()> {
+        if width == 0 || height == 0 {
+            return Err(PixelArtError::ZeroSizedImage { width, height });
+        }
+        Ok(())
+    }
 
-        for (row, col) in grid.iter_cells() {
-            let color = self.process_grid_cell(image, row, col, grid);
-            let (x, y, w, h) = grid.get_cell_bounds(row, col);
+    fn layout(&self, width: u32, height: u32) -> PixelArtResult<(Rect, Rect)> {
+        grid_region(width, height, self.crop, self.offset)
+    }
 
-            // Fill the cell with the extracted color using direct slice manipulation
-            for dy in 0..h {
-                let current_y = y + dy;
-                if current_y >= height {
-                    break;
-                }
+    fn resolve(&self, spec: &GridSpec, area: Rect) -> PixelArtResult<Grid> {
+        spec.resolve_with_aspect(area.width, area.height, self.pixel_aspect)
+    }
 
-                let row_start = (current_y * width + x) as usize * 4;
-                let max_dx = (width - x).min(w);
+    // Output size and the grid cells are painted with: the input size, or cells `scale`
+    // pixels tall and as wide as the pixel aspect makes them
+    fn output_grid(&self, grid: &Grid, width: u32, height: u32) -> (u32, u32, Grid) {
+        match self.scale {
+            Some(scale) => {
+                let cell_width = self.pixel_aspect.scaled_width(scale);
+                let (width, height) = (grid.width() * cell_width, grid.height() * scale);
+                (
+                    width,
+                    height,
+                    Grid::new(width, height, grid.width(), grid.height()),
+                )
+            }
+            None => (width, height, grid.clone()),
+        }
+    }
+
+    // The size `convert` produces for a `width` x `height` input
+    pub fn output_dimensions(&self, width: u32, height: u32) -> PixelArtResult<(u32, u32)> {
+        Self::check_size(width, height)?;
+        let (frame, area) = self.layout(width, height)?;
+        match (&self.strategy, self.scale) {
+            (ProcessingStrategy::UniformGrid(spec), Some(_)) => {
+                let grid = self.resolve(spec, area)?;
+                let (width, height, _) = self.output_grid(&grid, area.width, area.height);
+                Ok((width, height))
+            }
+            (ProcessingStrategy::UniformGrid(spec), None) => {
+                self.resolve(spec, area)?;
+                Ok((frame.width, frame.height))
+            }
+            (ProcessingStrategy::AdaptiveQuadTree(_), _) => Ok((frame.width, frame.height)),
+        }
+    }
+
+    pub fn convert(&self, image: &DynamicImage) -> PixelArtResult<DynamicImage> {
+        Ok(self.convert_with(image, false)?.0)
+    }
+
+    pub fn convert_parallel(&self, image: &DynamicImage) -> PixelArtResult<DynamicImage> {
+        Ok(self.convert_with(image, true)?.0)
+    }
+
+    // Like `convert_parallel`; in quadtree mode also returns the tree the result was
+    // rendered from, with nodes relative to the converted area
+    pub fn convert_parallel_with_tree(
+        &self,
+        image: &DynamicImage,
+    ) -> PixelArtResult<(DynamicImage, Option<QuadTree>)> {
+        self.convert_with(image, true)
+    }
+
+    fn convert_with(
+        &self,
+        image: &DynamicImage,
+        parallel: bool,
+    ) -> PixelArtResult<(DynamicImage, Option<QuadTree>)> {
+        Self::check_size(image.width(), image.height())?;
+        let (frame, area) = self.layout(image.width(), image.height())?;
+        let image = area_pixels(image, area);
+        let (result, tree) = match &self.strategy {
+            ProcessingStrategy::UniformGrid(spec) => {
+                let grid = self.resolve(spec, area)?;
+                (self.process_with_grid(&image, &grid, parallel)?, None)
+            }
+            ProcessingStrategy::AdaptiveQuadTree(config) => {
+                let tree = self.build_tree(&image, config, parallel, true);
+                let result = self.render_quadtree_to_image(&tree, image.width(), image.height());
+                (DynamicImage::ImageRgba8(result), Some(tree))
+            }
+        };
+        if self.scale.is_some() || area == frame {
+            return Ok((result, tree));
+        }
+
+        // Pixels of the frame before the offset stay transparent black
+        let mut framed = RgbaImage::new(frame.width, frame.height);
+        image::imageops::replace(
+            &mut framed,
+            &result.into_rgba8(),
+            (area.x - frame.x) as i64,
+            (area.y - frame.y) as i64,
+        );
+        Ok((DynamicImage::ImageRgba8(framed), tree))
+    }
+
+    // Grid mode over a row stream: one band of cell rows is read at a time and the output
+    // rows are written once every cell color is known, so memory stays at one band plus
+    // one color per cell. The sink receives `output_dimensions` rows, matching
+    // `convert_parallel`.
+    pub fn convert_streaming(
+        &self,
+        source: &mut dyn RowSource,
+        sink: &mut dyn RowSink,
+    ) -> PixelArtResult<()> {
+        let ProcessingStrategy::UniformGrid(spec) = &self.strategy else {
+            return Err(PixelArtError::StreamingRequiresGrid);
+        };
+        if self.layout != GridLayout::Rectangular {
+            return Err(PixelArtError::InvalidConfig(
+                "streaming needs a rectangular layout".to_string(),
+            ));
+        }
 
-                for dx in 0..max_dx {
-                    let idx = row_start + (dx as usize * 4);
-                    result_data[idx] = color.0[0]; // R
-                    result_data[idx + 1] = color.0[1]; // G
-                    result_data[idx + 2] = color.0[2]; // B
-                    result_data[idx + 3] = color.0[3]; // A
+        let (width, height) = source.dimensions();
+        Self::check_size(width, height)?;
+        let (frame, area) = self.layout(width, height)?;
+        let grid = self.resolve(spec, area)?;
+        let (cell_width, cell_height) = (grid.cell_width(), grid.cell_height());
+
+        let mut source_row = vec![0u8; width as usize * 4];
+        for _ in 0..area.y {
+            source.read_row(&mut source_row)?;
+        }
+
+        let area_bytes = area.x as usize * 4..(area.x + area.width) as usize * 4;
+        let columns = grid.width() as usize;
+        let mut colors = Vec::with_capacity(grid.cell_count() as usize);
+        if cell_width > 0 && cell_height > 0 {
+            let mut band = RgbaImage::new(area.width, cell_height);
+            for row in 0..grid.height() {
+                for line in band.chunks_exact_mut(area_bytes.len()) {
+                    source.read_row(&mut source_row)?;
+                    line.copy_from_slice(&source_row[area_bytes.clone()]);
                 }
+
+                let band_colors: Vec<Rgba<u8>> = (0..grid.width())
+                    .into_par_iter()
+                    .map(|col| {
+                        let view = PixelView::from_image(
+                            &band,
+                            col * cell_width,
+                            0,
+                            cell_width,
+                            cell_height,
+                        );
+                        let color = self.color_extractor.extract_color_view(view);
+                        if let Some(callback) = &self.progress_callback {
+                            callback(row, col);
+                        }
+                        color
+                    })
+                    .collect();
+                colors.extend(band_colors);
             }
+            self.apply_palette(&mut colors, columns);
+        }
 
-            if let Some(callback) = &self.progress_callback {
-                callback(row, col);
+        let (out_width, out_height, out_grid) = self.output_grid(&grid, area.width, area.height);
+        // Unscaled output is the frame, with the grid area at the offset inside it
+        let (frame_width, frame_height, left, top) = match self.scale {
+            Some(_) => (out_width, out_height, 0, 0),
+            None => (
+                frame.width,
+                frame.height,
+                (area.x - frame.x) as usize,
+                area.y - frame.y,
+            ),
+        };
+        let mut line = vec![0u8; frame_width as usize * 4];
+        for _ in 0..top {
+            sink.write_row(&line)?;
+        }
+        let mut written = top;
+        let cells = left * 4..(left + out_width as usize) * 4;
+        for row_colors in colors.chunks_exact(columns) {
+            fill_cell_row(
+                &mut line[cells.clone()],
+                row_colors,
+                out_grid.cell_width() as usize,
+            );
+            for _ in 0..out_grid.cell_height() {
+                sink.write_row(&line)?;
+                written += 1;
             }
         }
 
-        let result_image = RgbaImage::from_raw(width, height, result_data).unwrap();
-        Ok(DynamicImage::ImageRgba8(result_image))
+        // Rows below the grid stay transparent black, as in `fill_grid`
+        line.fill(0);
+        for _ in written..frame_height {
+            sink.write_row(&line)?;
+        }
+        sink.finish()
     }
 
-    fn process_with_grid_parallel(
+    fn process_with_grid(
         &self,
-        image: &DynamicImage,
+        image: &RgbaImage,
         grid: &Grid,
-    ) -> Result<DynamicImage> {
-        let width = image.width();
-        let height = image.height();
-        let mut result_data = vec![0u8; (width * height * 4) as usize];
+        parallel: bool,
+    ) -> PixelArtResult<DynamicImage> {
+        if self.layout != GridLayout::Rectangular {
+            let tiles = TiledGrid::new(self.layout, grid.clone());
+            return Ok(DynamicImage::ImageRgba8(
+                self.process_with_tiles(image, &tiles, parallel),
+            ));
+        }
+
+        let mut colors = if self.can_merge_uniform(grid) {
+            self.merged_cell_colors(image, grid, parallel)
+        } else if parallel {
+            self.parallel_cell_colors(image, grid)
+        } else {
+            grid.iter_cells()
+                .map(|(row, col)| {
+                    let color = self.process_grid_cell(image, row, col, grid);
+                    if let Some(callback) = &self.progress_callback {
+                        callback(row, col);
+                    }
+                    color
+                })
+                .collect()
+        };
+        self.apply_palette(&mut colors, grid.width() as usize);
+
+        let (width, height, out_grid) = self.output_grid(grid, image.width(), image.height());
+        let mut result_image = RgbaImage::new(width, height);
+        fill_grid(&mut result_image, &out_grid, &colors, parallel);
+        Ok(DynamicImage::ImageRgba8(result_image))
+    }
+
+    // Non-rectangular layouts: pixels are gathered per cell by membership, and painted
+    // back the same way. The output keeps the input size.
+    fn process_with_tiles(
+        &self,
+        image: &RgbaImage,
+        tiles: &TiledGrid,
+        parallel: bool,
+    ) -> RgbaImage {
+        let (width, height) = tiles.extent();
+        let columns = tiles.index_columns() as usize;
+        let slots = tiles.index_rows() as usize * columns;
+        let mut cell_pixels: Vec<Vec<Rgba<u8>>> = vec![Vec::new(); slots];
+        for y in 0..height {
+            for x in 0..width {
+                if let Some((row, col)) = tiles.cell_at(x, y) {
+                    cell_pixels[tiles.cell_index(row, col)].push(*image.get_pixel(x, y));
+                }
+            }
+        }
 
+        let cells: Vec<(u32, u32)> = tiles.iter_cells().collect();
+        let extract = |&(row, col): &(u32, u32)| {
+            let pixels = &cell_pixels[tiles.cell_index(row, col)];
+            let color = self.color_extractor.extract_color(pixels);
+            if let Some(callback) = &self.progress_callback {
+                callback(row, col);
+            }
+            color
+        };
+        let cell_colors: Vec<Rgba<u8>> = if parallel {
+            cells.par_iter().map(extract).collect()
+        } else {
+            cells.iter().map(extract).collect()
+        };
+        drop(cell_pixels);
+
+        // Slots that aren't cells copy their left (or upper) neighbour so that dithering
+        // sees unbroken rows
+        let mut colors = vec![Rgba([0, 0, 0, 0]); slots];
+        for (&(row, col), color) in cells.iter().zip(&cell_colors) {
+            colors[tiles.cell_index(row, col)] = *color;
+        }
+        for index in 0..slots {
+            let (row, col) = ((index / columns) as u32, (index % columns) as u32);
+            if !tiles.is_cell(row, col) {
+                colors[index] = if col > 0 {
+                    colors[index - 1]
+                } else {
+                    colors[index.saturating_sub(columns)]
+                };
+            }
+        }
+        if let Some(quantizer) = &self.palette_quantizer {
+            let palette = quantizer.build_palette(&cell_colors);
+            self.report_palette(&palette);
+            dither_to_palette(&mut colors, columns, &palette, self.dithering);
+        }
+
+        // The covered area lies inside the image, as the grid was resolved against it
+        let mut result_image = RgbaImage::new(image.width(), image.height());
+        let row_bytes = image.width() as usize * 4;
+        if row_bytes == 0 {
+            return result_image;
+        }
+        let paint_row = |(y, line): (usize, &mut [u8])| {
+            for x in 0..width {
+                if let Some((row, col)) = tiles.cell_at(x, y as u32) {
+                    let pixel = x as usize * 4;
+                    line[pixel..pixel + 4].copy_from_slice(&colors[tiles.cell_index(row, col)].0);
+                }
+            }
+        };
+        let painted = &mut result_image.as_mut()[..row_bytes * height as usize];
+        if parallel {
+            painted
+                .par_chunks_mut(row_bytes)
+                .enumerate()
+                .for_each(paint_row);
+        } else {
+            painted
+                .chunks_mut(row_bytes)
+                .enumerate()
+                .for_each(paint_row);
+        }
+        result_image
+    }
+
+    fn parallel_cell_colors(&self, image: &RgbaImage, grid: &Grid) -> Vec<Rgba<u8>> {
         let cells: Vec<(u32, u32)> = grid.iter_cells().collect();
-        let progress_counter = Arc::new(AtomicU32::new(0));
+        let progress_counter = AtomicU32::new(0);
 
-        let processed_cells: Vec<(u32, u32, Rgba<u8>)> = cells
+        cells
             .par_iter()
             .map(|(row, col)| {
                 let color = self.process_grid_cell(image, *row, *col, grid);
@@ -176,129 +590,329 @@ impl PixelArtConverter {
                 }
 
                 let completed = progress_counter.fetch_add(1, Ordering::Relaxed) + 1;
-                if completed % 10 == 0 {
+                if completed.is_multiple_of(10) {
                     // Throttle updates to avoid overwhelming the UI
                     std::thread::yield_now();
                 }
 
-                (*row, *col, color)
+                color
             })
-            .collect();
+            .collect()
+    }
 
-        for (row, col, color) in processed_cells {
-            let (x, y, w, h) = grid.get_cell_bounds(row, col);
+    fn can_merge_uniform(&self, grid: &Grid) -> bool {
+        self.merge_uniform && grid.cell_width() > 0 && grid.cell_height() > 0
+    }
 
-            // Fill the cell with the extracted color using direct slice manipulation
-            for dy in 0..h {
-                let current_y = y + dy;
-                if current_y >= height {
-                    break;
+    // Cell colors in `iter_cells` order. Leaves of a quadtree over the cell lattice are
+    // either a single flat color, so one extraction covers all their cells, or can't be
+    // split further, in which case their cells are extracted one by one.
+    fn merged_cell_colors(&self, image: &RgbaImage, grid: &Grid, parallel: bool) -> Vec<Rgba<u8>> {
+        let integral = IntegralImage::new(image, IntegralSpace::Rgb);
+        let context = SplitContext::new(image, &integral);
+        let config = QuadTreeConfig::new(u32::BITS, 0.0)
+            .with_criterion(Arc::new(UniformityCriterion))
+            .with_cell_size(grid.cell_width(), grid.cell_height())
+            .with_parallel_cutoff(if parallel {
+                DEFAULT_PARALLEL_CUTOFF
+            } else {
+                u32::MAX
+            });
+        let tree = QuadTree::build_on_lattice(&context, &config, grid.width(), grid.height());
+
+        let leaf_colors = |leaf: &&QuadNode| {
+            let col = leaf.x / grid.cell_width();
+            let row = leaf.y / grid.cell_height();
+            let cols = leaf.width / grid.cell_width();
+            let rows = leaf.height / grid.cell_height();
+            let uniform = context.is_uniform(leaf);
+            let first = self.process_grid_cell(image, row, col, grid);
+
+            let mut colors = Vec::with_capacity((cols * rows) as usize);
+            for r in row..row + rows {
+                for c in col..col + cols {
+                    let color = if uniform || (r == row && c == col) {
+                        first
+                    } else {
+                        self.process_grid_cell(image, r, c, grid)
+                    };
+                    if let Some(callback) = &self.progress_callback {
+                        callback(r, c);
+                    }
+                    colors.push(((r * grid.width() + c) as usize, color));
                 }
+            }
+            colors
+        };
+
+        let leaves = tree.leaves();
+        let assigned: Vec<(usize, Rgba<u8>)> = if parallel {
+            leaves.par_iter().flat_map_iter(leaf_colors).collect()
+        } else {
+            leaves.iter().flat_map(leaf_colors).collect()
+        };
+
+        let mut colors = vec![Rgba([0, 0, 0, 255]); grid.cell_count() as usize];
+        for (index, color) in assigned {
+            colors[index] = color;
+        }
+        colors
+    }
 
-                let row_start = (current_y * width + x) as usize * 4;
-                let max_dx = (width - x).min(w);
+    // The tree adaptive mode renders for `image`, palette indices included; None in grid mode.
+    // With a crop or offset, node bounds are relative to the area after the offset.
+    // Progress callbacks are not called.
+    pub fn build_quadtree(&self, image: &DynamicImage) -> Option<QuadTree> {
+        match &self.strategy {
+            ProcessingStrategy::UniformGrid(_) => None,
+            ProcessingStrategy::AdaptiveQuadTree(config) => {
+                let (_, area) = self.layout(image.width(), image.height()).ok()?;
+                Some(self.build_tree(&area_pixels(image, area), config, true, false))
+            }
+        }
+    }
 
-                for dx in 0..max_dx {
-                    let idx = row_start + (dx as usize * 4);
-                    result_data[idx] = color.0[0]; // R
-                    result_data[idx + 1] = color.0[1]; // G
-                    result_data[idx + 2] = color.0[2]; // B
-                    result_data[idx + 3] = color.0[3]; // A
+    fn build_tree(
+        &self,
+        image: &RgbaImage,
+        config: &QuadTreeConfig,
+        parallel: bool,
+        report_progress: bool,
+    ) -> QuadTree {
+        let mut config = config.clone();
+        if !parallel {
+            config.parallel_cutoff = u32::MAX;
+        }
+        if !report_progress {
+            config.progress = None;
+        } else if let Some(callback) = &self.tree_progress_callback {
+            config.progress = Some(Arc::clone(callback));
+        }
+        let mut quadtree = QuadTree::build_with_config(image, &config);
+        if let Some(quantizer) = &self.palette_quantizer {
+            // Large flat leaves should pull the palette harder than small detailed ones
+            let palette = quantizer.build_palette_weighted(&quadtree.weighted_leaf_colors());
+            self.report_palette(&palette);
+            quadtree.assign_palette(palette);
+        }
+        quadtree
+    }
+
+    fn process_grid_cell(&self, image: &RgbaImage, row: u32, col: u32, grid: &Grid) -> Rgba<u8> {
+        let (x, y, w, h) = grid.get_cell_bounds(row, col);
+        self.color_extractor
+            .extract_color_view(PixelView::from_image(image, x, y, w, h))
+    }
+
+    fn render_quadtree_to_image(&self, tree: &QuadTree, width: u32, height: u32) -> RgbaImage {
+        let mut result_image = RgbaImage::new(width, height);
+        // Leaves tile the image exactly, so no bounds checks are needed
+        for leaf in tree.leaves() {
+            let color = tree.leaf_color(leaf);
+            for py in leaf.y..leaf.y + leaf.height {
+                for px in leaf.x..leaf.x + leaf.width {
+                    result_image.put_pixel(px, py, color);
                 }
             }
         }
 
-        let result_image = RgbaImage::from_raw(width, height, result_data).unwrap();
-        Ok(DynamicImage::ImageRgba8(result_image))
+        result_image
     }
+}
 
-    fn process_with_quadtree(&self, image: &DynamicImage) -> Result<DynamicImage> {
-        let rgba_image = image.to_rgba8();
-        let quadtree = QuadTree::build(&rgba_image, 4, 50.0);
-
-        // Add progress callback support for quadtree processing
-        if let Some(callback) = &self.progress_callback {
-            let node_count = quadtree.node_count();
-            let processed = Arc::new(AtomicU32::new(0));
-            let callback_clone = Arc::clone(callback);
-            let processed_clone = Arc::clone(&processed);
-
-            quadtree.traverse_with_callback(|_node| {
-                let current = processed_clone.fetch_add(1, Ordering::Relaxed);
-                // Map node progress to grid coordinates for visualization
-                let progress_percent = (current * 100) / node_count;
-                let estimated_row = progress_percent / 10;
-                let estimated_col = progress_percent % 10;
-                callback_clone(estimated_row, estimated_col);
-            });
-        }
+// Collects converter options; `build` checks that they fit together. The grid is only a
+// specification here and is resolved against each image, so one converter can process
+// images of any size.
+#[derive(Default)]
+pub struct PixelArtConverterBuilder {
+    strategy: Option<ProcessingStrategy>,
+    color_extractor: Option<Box<dyn ColorExtractor>>,
+    palette_quantizer: Option<PaletteQuantizer>,
+    dithering: Dithering,
+    scale: Option<u32>,
+    crop: Option<Rect>,
+    offset: (u32, u32),
+    layout: GridLayout,
+    pixel_aspect: PixelAspect,
+    merge_uniform: bool,
+    progress_callback: Option<Arc<dyn Fn(u32, u32) + Send + Sync>>,
+    tree_progress_callback: Option<ProgressFn>,
+    palette_callback: Option<PaletteFn>,
+}
 
-        let result_image = self.render_quadtree_to_image(&quadtree, image.width(), image.height());
-        Ok(DynamicImage::ImageRgba8(result_image))
+impl PixelArtConverterBuilder {
+    // `columns` x `rows` cells, sized to each image
+    pub fn grid_cells(self, columns: u32, rows: u32) -> Self {
+        self.grid(GridSpec::Cells { columns, rows })
     }
 
-    fn process_grid_cell(&self, image: &DynamicImage, row: u32, col: u32, grid: &Grid) -> Rgba<u8> {
-        let bounds = grid.get_cell_bounds(row, col);
-        let rgba_image = image.to_rgba8();
+    // Cells of `width` x `height` source pixels
+    pub fn cell_size(self, width: u32, height: u32) -> Self {
+        self.grid(GridSpec::CellSize { width, height })
+    }
 
-        PIXEL_BUFFER.with(|buffer| {
-            let mut buffer = buffer.borrow_mut();
-            self.extract_cell_pixels_fast(&rgba_image, bounds, &mut buffer);
-            self.color_extractor.extract_color(&buffer)
-        })
+    // `columns` cells across, with as many rows as keep the image's aspect ratio
+    pub fn target_width(self, columns: u32) -> Self {
+        self.grid(GridSpec::TargetWidth { columns })
     }
 
-    fn extract_cell_pixels_fast(
-        &self,
-        image: &RgbaImage,
-        bounds: (u32, u32, u32, u32),
-        buffer: &mut Vec<Rgba<u8>>,
-    ) {
-        let (x, y, w, h) = bounds;
-        buffer.clear();
-        buffer.reserve((w * h) as usize);
-
-        // Use raw pointer access to avoid bounds checks
-        let img_width = image.width();
-        let img_height = image.height();
-        let pixels = image.as_raw();
-
-        for dy in 0..h {
-            let current_y = y + dy;
-            if current_y >= img_height {
-                break;
-            }
+    pub fn grid(self, spec: impl Into<GridSpec>) -> Self {
+        self.strategy(ProcessingStrategy::UniformGrid(spec.into()))
+    }
 
-            let row_start = (current_y * img_width + x) as usize * 4;
-            let max_dx = (img_width - x).min(w);
-
-            for dx in 0..max_dx {
-                let idx = row_start + (dx as usize * 4);
-                unsafe {
-                    let r = *pixels.get_unchecked(idx);
-                    let g = *pixels.get_unchecked(idx + 1);
-                    let b = *pixels.get_unchecked(idx + 2);
-                    let a = *pixels.get_unchecked(idx + 3);
-                    buffer.push(Rgba([r, g, b, a]));
-                }
-            }
-        }
+    pub fn quadtree(self, config: QuadTreeConfig) -> Self {
+        self.strategy(ProcessingStrategy::AdaptiveQuadTree(config))
     }
 
-    fn render_quadtree_to_image(&self, tree: &QuadTree, width: u32, height: u32) -> RgbaImage {
-        let mut result_image = RgbaImage::new(width, height);
-        let cells = tree.to_grid_cells();
+    pub fn strategy(mut self, strategy: ProcessingStrategy) -> Self {
+        self.strategy = Some(strategy);
+        self
+    }
 
-        for (x, y, w, h, color) in cells {
-            for dy in 0..h {
-                for dx in 0..w {
-                    if x + dx < width && y + dy < height {
-                        result_image.put_pixel(x + dx, y + dy, color);
-                    }
-                }
+    // Defaults to `AverageColorExtractor`
+    pub fn extractor(self, extractor: impl ColorExtractor + 'static) -> Self {
+        self.boxed_extractor(Box::new(extractor))
+    }
+
+    pub fn boxed_extractor(mut self, extractor: Box<dyn ColorExtractor>) -> Self {
+        self.color_extractor = Some(extractor);
+        self
+    }
+
+    pub fn palette(mut self, quantizer: PaletteQuantizer) -> Self {
+        self.palette_quantizer = Some(quantizer);
+        self
+    }
+
+    // Grid mode with a palette: how cell colors are mapped onto it
+    pub fn dithering(mut self, dithering: Dithering) -> Self {
+        self.dithering = dithering;
+        self
+    }
+
+    // Grid mode: output `scale` x `scale` pixels per cell instead of the input size
+    pub fn scale(mut self, scale: u32) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    // Convert only this region; the output is the region's size unless scaled
+    pub fn crop(mut self, region: Rect) -> Self {
+        self.crop = Some(region);
+        self
+    }
+
+    // Start the grid this many pixels right of and below the image (or crop) origin, to
+    // line cells up with features in the source. Pixels skipped stay transparent.
+    pub fn offset(mut self, x: u32, y: u32) -> Self {
+        self.offset = (x, y);
+        self
+    }
+
+    // Grid mode: the shape of the cells; each keeps the grid's cell size
+    pub fn layout(mut self, layout: GridLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    // Grid mode: width:height of the pixels on the target display. Cells sized from the
+    // image (target width or auto) get that shape, and scaled output draws each cell
+    // that many times as wide as tall, so the art looks right on a wide-pixel display.
+    pub fn pixel_aspect(mut self, aspect: PixelAspect) -> Self {
+        self.pixel_aspect = aspect;
+        self
+    }
+
+    pub fn merge_uniform(mut self, merge_uniform: bool) -> Self {
+        self.merge_uniform = merge_uniform;
+        self
+    }
+
+    // Grid mode: called with (row, col) as each cell finishes
+    pub fn progress_callback(mut self, callback: Arc<dyn Fn(u32, u32) + Send + Sync>) -> Self {
+        self.progress_callback = Some(callback);
+        self
+    }
+
+    // Quadtree mode: called with (pixels done, pixels total) as leaves are finalized
+    pub fn tree_progress_callback(mut self, callback: ProgressFn) -> Self {
+        self.tree_progress_callback = Some(callback);
+        self
+    }
+
+    // With a palette: called with the palette of each conversion, in palette order
+    pub fn palette_callback(mut self, callback: PaletteFn) -> Self {
+        self.palette_callback = Some(callback);
+        self
+    }
+
+    pub fn build(self) -> PixelArtResult<PixelArtConverter> {
+        let invalid = |message: &str| Err(PixelArtError::InvalidConfig(message.to_string()));
+
+        let Some(strategy) = self.strategy else {
+            return invalid("choose a grid or a quadtree");
+        };
+        let grid_mode = match &strategy {
+            ProcessingStrategy::UniformGrid(spec) => {
+                spec.validate()?;
+                true
+            }
+            ProcessingStrategy::AdaptiveQuadTree(_) => false,
+        };
+
+        if self.scale == Some(0) {
+            return invalid("scale must be at least 1");
+        }
+        if !grid_mode && self.scale.is_some() {
+            return invalid("scale needs a grid");
+        }
+        if let Some(crop) = self.crop
+            && (crop.width == 0 || crop.height == 0)
+        {
+            return invalid("crop region must not be empty");
+        }
+        if self.layout != GridLayout::Rectangular {
+            if !grid_mode {
+                return invalid("layouts need a grid");
+            }
+            if self.scale.is_some() || self.merge_uniform {
+                return invalid("scale and merge_uniform need a rectangular layout");
+            }
+        }
+        if self.pixel_aspect.width == 0 || self.pixel_aspect.height == 0 {
+            return invalid("pixel aspect must be positive");
+        }
+        if !grid_mode && !self.pixel_aspect.is_square() {
+            return invalid("pixel aspect needs a grid");
+        }
+        if !grid_mode && self.merge_uniform {
+            return invalid("merge_uniform needs a grid");
+        }
+        if self.dithering != Dithering::None {
+            if !grid_mode {
+                return invalid("dithering needs a grid");
+            }
+            if self.palette_quantizer.is_none() {
+                return invalid("dithering needs a palette");
             }
         }
 
-        result_image
+        let extractor = self
+            .color_extractor
+            .unwrap_or_else(|| Box::new(AverageColorExtractor));
+        Ok(PixelArtConverter {
+            progress_callback: self.progress_callback,
+            tree_progress_callback: self.tree_progress_callback,
+            palette_callback: self.palette_callback,
+            palette_quantizer: self.palette_quantizer,
+            merge_uniform: self.merge_uniform,
+            dithering: self.dithering,
+            scale: self.scale,
+            crop: self.crop,
+            offset: self.offset,
+            layout: self.layout,
+            pixel_aspect: self.pixel_aspect,
+            ..PixelArtConverter::new(strategy, extractor)
+        })
     }
 }
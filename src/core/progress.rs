@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+// Receives (done, total) units of work; called from worker threads, with `done`
+// increasing from call to call
+pub type ProgressFn = Arc<dyn Fn(u64, u64) + Send + Sync>;
+
+// Reports at most about this many updates per run
+const PROGRESS_STEPS: u64 = 1000;
+
+// Counts finished work (e.g. pixels covered by final quadtree leaves) across threads
+pub struct ProgressTracker {
+    done: AtomicU64,
+    total: u64,
+    // Highest value passed to the callback so far
+    reported: AtomicU64,
+    step: u64,
+    // Held while calling back, so threads cannot overtake each other's reports
+    calls: Mutex<()>,
+    callback: ProgressFn,
+}
+
+impl ProgressTracker {
+    pub fn new(total: u64, callback: ProgressFn) -> Self {
+        Self {
+            done: AtomicU64::new(0),
+            total,
+            reported: AtomicU64::new(0),
+            step: (total / PROGRESS_STEPS).max(1),
+            calls: Mutex::new(()),
+            callback,
+        }
+    }
+
+    // Calls back once another step of work is done, and when all of it is
+    pub fn advance(&self, amount: u64) {
+        let done = (self.done.fetch_add(amount, Ordering::Relaxed) + amount).min(self.total);
+        let reported = self.reported.load(Ordering::Relaxed);
+        if done < self.total && done < reported.saturating_add(self.step) {
+            return;
+        }
+
+        let _calls = self.calls.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.reported.fetch_max(done, Ordering::Relaxed) >= done {
+            return;
+        }
+        (self.callback)(done, self.total);
+    }
+
+    pub fn done(&self) -> u64 {
+        self.done.load(Ordering::Relaxed).min(self.total)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
@@ -1,11 +1,33 @@
 use crate::core::color::calculate_color_variance;
+use crate::core::error::PixelArtResult;
+use crate::core::integral::{IntegralImage, IntegralSpace};
+use crate::core::progress::{ProgressFn, ProgressTracker};
+use crate::core::quantization::{MedianCutQuantizer, nearest_palette_index};
+use crate::core::split::{SplitContext, SplitCriterion, VarianceCriterion};
 use image::{Rgba, RgbaImage};
+use serde::Serialize;
+use std::sync::Arc;
+
+pub const DEFAULT_PARALLEL_CUTOFF: u32 = 64 * 64;
+
+// Overlay border colors, indexed by node depth and repeating past the end
+pub const DEPTH_COLORS: [Rgba<u8>; 8] = [
+    Rgba([255, 255, 255, 255]),
+    Rgba([255, 64, 64, 255]),
+    Rgba([255, 160, 0, 255]),
+    Rgba([255, 255, 0, 255]),
+    Rgba([64, 255, 64, 255]),
+    Rgba([0, 224, 255, 255]),
+    Rgba([64, 96, 255, 255]),
+    Rgba([224, 64, 255, 255]),
+];
 
 #[derive(Debug)]
 pub struct QuadNode {
     pub x: u32,
     pub y: u32,
-    pub size: u32,
+    pub width: u32,
+    pub height: u32,
     pub mean_color: Rgba<u8>,
     pub variance: f64,
     pub palette_idx: Option<u8>,
@@ -13,11 +35,19 @@ pub struct QuadNode {
 }
 
 impl QuadNode {
-    pub fn new(x: u32, y: u32, size: u32, mean_color: Rgba<u8>, variance: f64) -> Self {
+    pub fn new(
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        mean_color: Rgba<u8>,
+        variance: f64,
+    ) -> Self {
         Self {
             x,
             y,
-            size,
+            width,
+            height,
             mean_color,
             variance,
             palette_idx: None,
@@ -26,81 +56,337 @@ impl QuadNode {
     }
 }
 
+#[derive(Clone)]
+pub struct QuadTreeConfig {
+    pub max_depth: u32,
+    pub criterion: Arc<dyn SplitCriterion>,
+    // Nodes are only split when every child is at least this many cells on each side
+    pub min_node_size: u32,
+    // Nodes smaller than this many pixels are built on the current thread
+    pub parallel_cutoff: u32,
+    // Node bounds snap to a lattice of cells this size; (1, 1) splits down to pixels.
+    // Pixels past the last whole cell are not covered, as in grid mode.
+    pub cell_size: (u32, u32),
+    // Split leaves until adjacent leaves differ by at most one level (a 2:1 size ratio)
+    pub balance: bool,
+    // Receives (pixels covered by finished leaves, pixels covered by the tree) while building
+    pub progress: Option<ProgressFn>,
+}
+
+impl QuadTreeConfig {
+    pub fn new(max_depth: u32, variance_threshold: f64) -> Self {
+        Self {
+            max_depth,
+            criterion: Arc::new(VarianceCriterion {
+                threshold: variance_threshold,
+            }),
+            min_node_size: 1,
+            parallel_cutoff: DEFAULT_PARALLEL_CUTOFF,
+            cell_size: (1, 1),
+            balance: false,
+            progress: None,
+        }
+    }
+
+    pub fn with_criterion(mut self, criterion: Arc<dyn SplitCriterion>) -> Self {
+        self.criterion = criterion;
+        self
+    }
+
+    pub fn with_min_node_size(mut self, min_node_size: u32) -> Self {
+        self.min_node_size = min_node_size.max(1);
+        self
+    }
+
+    pub fn with_parallel_cutoff(mut self, parallel_cutoff: u32) -> Self {
+        self.parallel_cutoff = parallel_cutoff;
+        self
+    }
+
+    pub fn with_cell_size(mut self, cell_width: u32, cell_height: u32) -> Self {
+        self.cell_size = (cell_width.max(1), cell_height.max(1));
+        self
+    }
+
+    pub fn with_balance(mut self, balance: bool) -> Self {
+        self.balance = balance;
+        self
+    }
+
+    pub fn with_progress(mut self, progress: ProgressFn) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+}
+
 pub struct QuadTree {
     pub root: QuadNode,
     pub max_depth: u32,
     pub variance_threshold: f64,
+    // Colors referenced by leaf `palette_idx` values; empty until a palette is assigned
+    pub palette: Vec<Rgba<u8>>,
 }
 
 impl QuadTree {
     pub fn build(image: &RgbaImage, max_depth: u32, variance_threshold: f64) -> Self {
-        let width = image.width();
-        let height = image.height();
-        let size = width.max(height);
+        let config =
+            QuadTreeConfig::new(max_depth, variance_threshold).with_parallel_cutoff(u32::MAX);
+        Self::build_with_config(image, &config)
+    }
 
-        let region = ImageRegion::new(image, 0, 0, size);
-        let root = Self::build_recursive(image, region, max_depth, variance_threshold);
+    // Same tree as `build`, with subtrees of at least `parallel_cutoff` pixels built on rayon
+    pub fn build_parallel(
+        image: &RgbaImage,
+        max_depth: u32,
+        variance_threshold: f64,
+        parallel_cutoff: u32,
+    ) -> Self {
+        let config = QuadTreeConfig::new(max_depth, variance_threshold)
+            .with_parallel_cutoff(parallel_cutoff);
+        Self::build_with_config(image, &config)
+    }
 
-        Self {
+    // Node statistics come from a summed-area table, so no pixels are copied per node
+    pub fn build_with_config(image: &RgbaImage, config: &QuadTreeConfig) -> Self {
+        let integral = IntegralImage::new(image, IntegralSpace::Lab);
+        let context = SplitContext::new(image, &integral);
+        let (cell_width, cell_height) = config.cell_size;
+        Self::build_on_lattice(
+            &context,
+            config,
+            image.width() / cell_width,
+            image.height() / cell_height,
+        )
+    }
+
+    // Builds over the first `columns` x `rows` lattice cells, taking node statistics
+    // from the context's integral image
+    pub fn build_on_lattice(
+        context: &SplitContext,
+        config: &QuadTreeConfig,
+        columns: u32,
+        rows: u32,
+    ) -> Self {
+        let (cell_width, cell_height) = config.cell_size;
+        let covered = columns as u64 * cell_width as u64 * rows as u64 * cell_height as u64;
+        let tracker = config
+            .progress
+            .clone()
+            .map(|callback| ProgressTracker::new(covered, callback));
+
+        let root = Self::build_recursive(
+            context,
+            config,
+            tracker.as_ref(),
+            (0, 0, columns, rows),
+            config.max_depth,
+        );
+
+        let mut tree = Self {
             root,
-            max_depth,
-            variance_threshold,
+            max_depth: config.max_depth,
+            variance_threshold: config.criterion.threshold(),
+            palette: Vec::new(),
+        };
+        if config.balance {
+            tree.balance(context, config);
         }
+        tree
     }
 
+    // `cells` is in lattice units; nodes store pixel bounds
     fn build_recursive(
-        image: &RgbaImage,
-        region: ImageRegion,
+        context: &SplitContext,
+        config: &QuadTreeConfig,
+        tracker: Option<&ProgressTracker>,
+        cells: (u32, u32, u32, u32),
         max_depth: u32,
-        variance_threshold: f64,
     ) -> QuadNode {
-        let (mean_color, variance) = calculate_region_variance(&region.pixels);
-
-        let mut node = QuadNode::new(region.x, region.y, region.size, mean_color, variance);
-
-        if should_split_node(&node, max_depth, variance_threshold) && region.size > 1 {
-            let half_size = region.size / 2;
-
-            let child_regions = [
-                ImageRegion::new(image, region.x, region.y, half_size),
-                ImageRegion::new(image, region.x + half_size, region.y, half_size),
-                ImageRegion::new(image, region.x, region.y + half_size, half_size),
-                ImageRegion::new(image, region.x + half_size, region.y + half_size, half_size),
-            ];
-
-            let children = [
-                Self::build_recursive(
-                    image,
-                    child_regions[0].clone(),
-                    max_depth - 1,
-                    variance_threshold,
-                ),
-                Self::build_recursive(
-                    image,
-                    child_regions[1].clone(),
-                    max_depth - 1,
-                    variance_threshold,
-                ),
-                Self::build_recursive(
-                    image,
-                    child_regions[2].clone(),
-                    max_depth - 1,
-                    variance_threshold,
-                ),
-                Self::build_recursive(
-                    image,
-                    child_regions[3].clone(),
-                    max_depth - 1,
-                    variance_threshold,
-                ),
-            ];
+        let (cx, cy, cw, ch) = cells;
+        let mut node = Self::leaf_node(context, config, cells);
+
+        if max_depth > 0
+            && Self::can_split(config, cw, ch)
+            && config.criterion.should_split(context, &node)
+        {
+            let quadrants = split_bounds(cx, cy, cw, ch);
+            let build_child = |child_cells| {
+                Self::build_recursive(context, config, tracker, child_cells, max_depth - 1)
+            };
+
+            let children =
+                if (node.width as u64 * node.height as u64) >= config.parallel_cutoff as u64 {
+                    let ((c0, c1), (c2, c3)) = rayon::join(
+                        || rayon::join(|| build_child(quadrants[0]), || build_child(quadrants[1])),
+                        || rayon::join(|| build_child(quadrants[2]), || build_child(quadrants[3])),
+                    );
+                    [c0, c1, c2, c3]
+                } else {
+                    quadrants.map(build_child)
+                };
 
             node.children = Some(Box::new(children));
+        } else if let Some(tracker) = tracker {
+            tracker.advance(node.width as u64 * node.height as u64);
         }
 
         node
     }
 
+    fn leaf_node(
+        context: &SplitContext,
+        config: &QuadTreeConfig,
+        cells: (u32, u32, u32, u32),
+    ) -> QuadNode {
+        let (cx, cy, cw, ch) = cells;
+        let (cell_width, cell_height) = config.cell_size;
+        let (x, y, width, height) = (
+            cx * cell_width,
+            cy * cell_height,
+            cw * cell_width,
+            ch * cell_height,
+        );
+        let (mean_color, variance) = context.integral().region_stats(x, y, width, height);
+        QuadNode::new(x, y, width, height, mean_color, variance)
+    }
+
+    // Children take the floor half of each side, so this keeps all four non-empty
+    fn can_split(config: &QuadTreeConfig, columns: u32, rows: u32) -> bool {
+        let min_size = config.min_node_size.max(1);
+        columns / 2 >= min_size && rows / 2 >= min_size
+    }
+
+    // Splits leaves next to leaves more than one level deeper until no such pair is left.
+    // Leaves too small to split under `config` are kept, so the ratio is best effort there.
+    pub fn balance(&mut self, context: &SplitContext, config: &QuadTreeConfig) {
+        let (cell_width, cell_height) = config.cell_size;
+        loop {
+            let mut leaves = Vec::new();
+            Self::collect_with_depth(&self.root, 0, &mut leaves);
+
+            let coarse: Vec<(u32, u32)> = leaves
+                .iter()
+                .filter(|&&(leaf, depth)| {
+                    leaf.children.is_none()
+                        && Self::can_split(
+                            config,
+                            leaf.width / cell_width,
+                            leaf.height / cell_height,
+                        )
+                        && DIRECTIONS.iter().any(|&direction| {
+                            self.neighbors_with_depth(leaf, direction)
+                                .iter()
+                                .any(|&(_, neighbor_depth)| neighbor_depth > depth + 1)
+                        })
+                })
+                .map(|&(leaf, _)| (leaf.x, leaf.y))
+                .collect();
+
+            if coarse.is_empty() {
+                break;
+            }
+
+            for (x, y) in coarse {
+                let leaf = Self::leaf_at_mut(&mut self.root, x, y);
+                let cells = (
+                    leaf.x / cell_width,
+                    leaf.y / cell_height,
+                    leaf.width / cell_width,
+                    leaf.height / cell_height,
+                );
+                let children = split_bounds(cells.0, cells.1, cells.2, cells.3)
+                    .map(|child_cells| Self::leaf_node(context, config, child_cells));
+                leaf.children = Some(Box::new(children));
+            }
+        }
+
+        if !self.palette.is_empty() {
+            let palette = std::mem::take(&mut self.palette);
+            self.assign_palette(palette);
+        }
+    }
+
+    fn leaf_at_mut(node: &mut QuadNode, x: u32, y: u32) -> &mut QuadNode {
+        if node.children.is_none() {
+            return node;
+        }
+        let child = node
+            .children
+            .as_deref_mut()
+            .into_iter()
+            .flatten()
+            .find(|child| contains(child, x, y))
+            .expect("children tile their parent");
+        Self::leaf_at_mut(child, x, y)
+    }
+
+    // The leaf covering pixel (x, y), if the tree covers it
+    pub fn leaf_at(&self, x: u32, y: u32) -> Option<&QuadNode> {
+        let mut node = &self.root;
+        if !contains(node, x, y) {
+            return None;
+        }
+        while let Some(children) = &node.children {
+            node = children.iter().find(|child| contains(child, x, y))?;
+        }
+        Some(node)
+    }
+
+    // Leaves sharing an edge with `leaf` on the given side
+    pub fn neighbors(&self, leaf: &QuadNode, direction: Direction) -> Vec<&QuadNode> {
+        self.neighbors_with_depth(leaf, direction)
+            .into_iter()
+            .map(|(neighbor, _)| neighbor)
+            .collect()
+    }
+
+    pub fn all_neighbors(&self, leaf: &QuadNode) -> Vec<&QuadNode> {
+        DIRECTIONS
+            .iter()
+            .flat_map(|&direction| self.neighbors(leaf, direction))
+            .collect()
+    }
+
+    fn neighbors_with_depth(&self, leaf: &QuadNode, direction: Direction) -> Vec<(&QuadNode, u32)> {
+        // One pixel wide strip just outside the given edge
+        let strip = match direction {
+            Direction::North if leaf.y > 0 => (leaf.x, leaf.y - 1, leaf.width, 1),
+            Direction::South => (leaf.x, leaf.y + leaf.height, leaf.width, 1),
+            Direction::West if leaf.x > 0 => (leaf.x - 1, leaf.y, 1, leaf.height),
+            Direction::East => (leaf.x + leaf.width, leaf.y, 1, leaf.height),
+            _ => return Vec::new(),
+        };
+
+        let mut found = Vec::new();
+        Self::collect_leaves_in(&self.root, 0, strip, &mut found);
+        found
+    }
+
+    fn collect_leaves_in<'a>(
+        node: &'a QuadNode,
+        depth: u32,
+        rect: (u32, u32, u32, u32),
+        found: &mut Vec<(&'a QuadNode, u32)>,
+    ) {
+        let (x, y, width, height) = rect;
+        let overlaps = node.x < x + width
+            && x < node.x + node.width
+            && node.y < y + height
+            && y < node.y + node.height;
+        if !overlaps {
+            return;
+        }
+
+        match &node.children {
+            Some(children) => {
+                for child in children.iter() {
+                    Self::collect_leaves_in(child, depth + 1, rect, found);
+                }
+            }
+            None => found.push((node, depth)),
+        }
+    }
+
     pub fn get_max_depth(&self) -> u32 {
         self.get_node_depth(&self.root)
     }
@@ -129,23 +415,87 @@ impl QuadTree {
         }
     }
 
-    pub fn quantize_with_palette(&mut self, _palette_size: u32) {
-        // Simple implementation: assign palette indices to leaf nodes
-        let mut palette_index = 0;
-        Self::assign_palette_indices(&mut self.root, &mut palette_index);
+    // Builds a palette of at most `palette_size` colors (capped at 256) from the leaf
+    // colors weighted by area, then points every leaf at its nearest entry
+    pub fn quantize_with_palette(&mut self, palette_size: u32) -> &[Rgba<u8>] {
+        let palette = MedianCutQuantizer::quantize_weighted(
+            &self.weighted_leaf_colors(),
+            palette_size.min(256),
+        );
+        self.assign_palette(palette);
+        &self.palette
+    }
+
+    // Points every leaf at its nearest entry of `palette`, which is truncated to 256 colors
+    pub fn assign_palette(&mut self, mut palette: Vec<Rgba<u8>>) {
+        palette.truncate(256);
+        if palette.is_empty() {
+            Self::clear_palette_indices(&mut self.root);
+        } else {
+            Self::assign_palette_indices(&mut self.root, &palette);
+        }
+        self.palette = palette;
     }
 
-    fn assign_palette_indices(node: &mut QuadNode, palette_index: &mut u8) {
+    fn assign_palette_indices(node: &mut QuadNode, palette: &[Rgba<u8>]) {
         if let Some(children) = &mut node.children {
             for child in children.iter_mut() {
-                Self::assign_palette_indices(child, palette_index);
+                Self::assign_palette_indices(child, palette);
             }
         } else {
-            node.palette_idx = Some(*palette_index);
-            *palette_index = palette_index.wrapping_add(1);
+            node.palette_idx = Some(nearest_palette_index(&node.mean_color, palette) as u8);
         }
     }
 
+    fn clear_palette_indices(node: &mut QuadNode) {
+        node.palette_idx = None;
+        if let Some(children) = &mut node.children {
+            for child in children.iter_mut() {
+                Self::clear_palette_indices(child);
+            }
+        }
+    }
+
+    // Leaf mean colors paired with their pixel area
+    pub fn weighted_leaf_colors(&self) -> Vec<(Rgba<u8>, u32)> {
+        self.leaves()
+            .into_iter()
+            .map(|leaf| (leaf.mean_color, leaf.width.saturating_mul(leaf.height)))
+            .collect()
+    }
+
+    pub fn palette(&self) -> &[Rgba<u8>] {
+        &self.palette
+    }
+
+    // Leaf color after palette assignment, or the mean color if the leaf has no index
+    pub fn leaf_color(&self, node: &QuadNode) -> Rgba<u8> {
+        node.palette_idx
+            .and_then(|idx| self.palette.get(idx as usize))
+            .copied()
+            .unwrap_or(node.mean_color)
+    }
+
+    // Row-major palette indices covering the root, or None before a palette is assigned
+    pub fn index_map(&self) -> Option<Vec<u8>> {
+        if !self.has_palette_assignments() {
+            return None;
+        }
+
+        let (origin_x, origin_y) = (self.root.x, self.root.y);
+        let width = self.root.width as usize;
+        let mut indices = vec![0u8; width * self.root.height as usize];
+        for leaf in self.leaves() {
+            let idx = leaf.palette_idx.unwrap_or(0);
+            for y in leaf.y..leaf.y + leaf.height {
+                let row = (y - origin_y) as usize * width;
+                let start = row + (leaf.x - origin_x) as usize;
+                indices[start..start + leaf.width as usize].fill(idx);
+            }
+        }
+        Some(indices)
+    }
+
     pub fn has_palette_assignments(&self) -> bool {
         self.node_has_palette_assignments(&self.root)
     }
@@ -203,6 +553,22 @@ impl QuadTree {
         }
     }
 
+    pub fn leaves(&self) -> Vec<&QuadNode> {
+        let mut leaves = Vec::new();
+        Self::collect_leaves(&self.root, &mut leaves);
+        leaves
+    }
+
+    fn collect_leaves<'a>(node: &'a QuadNode, leaves: &mut Vec<&'a QuadNode>) {
+        if let Some(children) = &node.children {
+            for child in children.iter() {
+                Self::collect_leaves(child, leaves);
+            }
+        } else {
+            leaves.push(node);
+        }
+    }
+
     pub fn to_grid_cells(&self) -> Vec<(u32, u32, u32, u32, Rgba<u8>)> {
         let mut cells = Vec::new();
         self.collect_leaf_cells(&self.root, &mut cells);
@@ -215,7 +581,7 @@ impl QuadTree {
                 Self::collect_leaf_cells_static(child, cells);
             }
         } else {
-            cells.push((node.x, node.y, node.size, node.size, node.mean_color));
+            cells.push((node.x, node.y, node.width, node.height, node.mean_color));
         }
     }
 
@@ -225,37 +591,183 @@ impl QuadTree {
                 Self::collect_leaf_cells_static(child, cells);
             }
         } else {
-            cells.push((node.x, node.y, node.size, node.size, node.mean_color));
+            cells.push((node.x, node.y, node.width, node.height, node.mean_color));
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TreeExport {
+    max_depth: u32,
+    variance_threshold: f64,
+    node_count: u32,
+    palette: Vec<[u8; 4]>,
+    root: NodeExport,
+}
+
+#[derive(Serialize)]
+struct NodeExport {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    depth: u32,
+    variance: f64,
+    color: [u8; 4],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    palette_idx: Option<u8>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<NodeExport>,
+}
+
+impl NodeExport {
+    fn new(node: &QuadNode, depth: u32) -> Self {
+        let children = node
+            .children
+            .iter()
+            .flat_map(|children| children.iter())
+            .map(|child| Self::new(child, depth + 1))
+            .collect();
+
+        Self {
+            x: node.x,
+            y: node.y,
+            width: node.width,
+            height: node.height,
+            depth,
+            variance: node.variance,
+            color: node.mean_color.0,
+            palette_idx: node.palette_idx,
+            children,
+        }
+    }
+}
+
+impl QuadTree {
+    // Nested JSON of every node's bounds, depth, variance and color, for inspecting how
+    // an image was divided
+    pub fn to_json(&self) -> PixelArtResult<String> {
+        let export = TreeExport {
+            max_depth: self.max_depth,
+            variance_threshold: self.variance_threshold,
+            node_count: self.node_count(),
+            palette: self.palette.iter().map(|color| color.0).collect(),
+            root: NodeExport::new(&self.root, 0),
+        };
+        Ok(serde_json::to_string_pretty(&export)?)
+    }
+
+    // Draws every node's outline over `base`, colored by depth via `DEPTH_COLORS`.
+    // Deeper nodes are drawn last so fine subdivisions stay visible.
+    pub fn render_overlay(&self, base: &RgbaImage) -> RgbaImage {
+        let mut overlay = base.clone();
+        let mut nodes = Vec::new();
+        Self::collect_with_depth(&self.root, 0, &mut nodes);
+        nodes.sort_by_key(|&(_, depth)| depth);
+
+        for (node, depth) in nodes {
+            let color = DEPTH_COLORS[depth as usize % DEPTH_COLORS.len()];
+            draw_outline(&mut overlay, node, color);
+        }
+        overlay
+    }
+
+    fn collect_with_depth<'a>(
+        node: &'a QuadNode,
+        depth: u32,
+        nodes: &mut Vec<(&'a QuadNode, u32)>,
+    ) {
+        nodes.push((node, depth));
+        if let Some(children) = &node.children {
+            for child in children.iter() {
+                Self::collect_with_depth(child, depth + 1, nodes);
+            }
         }
     }
 }
 
+// One-pixel rectangle outline clipped to the image
+fn draw_outline(image: &mut RgbaImage, node: &QuadNode, color: Rgba<u8>) {
+    if node.width == 0 || node.height == 0 || image.width() == 0 || image.height() == 0 {
+        return;
+    }
+    let right = (node.x + node.width - 1).min(image.width() - 1);
+    let bottom = (node.y + node.height - 1).min(image.height() - 1);
+    if node.x > right || node.y > bottom {
+        return;
+    }
+
+    for x in node.x..=right {
+        image.put_pixel(x, node.y, color);
+        image.put_pixel(x, bottom, color);
+    }
+    for y in node.y..=bottom {
+        image.put_pixel(node.x, y, color);
+        image.put_pixel(right, y, color);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::East,
+    Direction::West,
+];
+
+fn contains(node: &QuadNode, x: u32, y: u32) -> bool {
+    x >= node.x && x < node.x + node.width && y >= node.y && y < node.y + node.height
+}
+
+// Splits a rectangle into four quadrants that tile it exactly; odd sizes give the
+// extra row/column to the right and bottom quadrants
+pub fn split_bounds(x: u32, y: u32, width: u32, height: u32) -> [(u32, u32, u32, u32); 4] {
+    let left = width / 2;
+    let top = height / 2;
+    let right = width - left;
+    let bottom = height - top;
+
+    [
+        (x, y, left, top),
+        (x + left, y, right, top),
+        (x, y + top, left, bottom),
+        (x + left, y + top, right, bottom),
+    ]
+}
+
 #[derive(Debug, Clone)]
 pub struct ImageRegion {
     pub x: u32,
     pub y: u32,
-    pub size: u32,
+    pub width: u32,
+    pub height: u32,
     pub pixels: Vec<Rgba<u8>>,
 }
 
 impl ImageRegion {
-    pub fn new(image: &RgbaImage, x: u32, y: u32, size: u32) -> Self {
-        let mut pixels = Vec::new();
-        let img_width = image.width();
-        let img_height = image.height();
-
-        for dy in 0..size {
-            for dx in 0..size {
-                let px = x + dx;
-                let py = y + dy;
-
-                if px < img_width && py < img_height {
-                    pixels.push(*image.get_pixel(px, py));
-                }
+    pub fn new(image: &RgbaImage, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+
+        for py in y..(y + height).min(image.height()) {
+            for px in x..(x + width).min(image.width()) {
+                pixels.push(*image.get_pixel(px, py));
             }
         }
 
-        Self { x, y, size, pixels }
+        Self {
+            x,
+            y,
+            width,
+            height,
+            pixels,
+        }
     }
 }
 
@@ -1,5 +1,7 @@
 use crate::core::color::AverageColorExtractor;
 use crate::core::color::ColorExtractor;
+use crate::core::color::color_distance_lab_fast;
+use crate::core::palette::{PaletteOrder, sort_palette};
 use image::Rgba;
 use std::collections::HashMap;
 
@@ -111,6 +113,58 @@ impl MedianCutQuantizer {
             .collect()
     }
 
+    // Median cut over (color, weight) pairs: buckets split at their weighted median and
+    // are represented by their weighted mean. Unit weights give the same result as `quantize`.
+    pub fn quantize_weighted(colors: &[(Rgba<u8>, u32)], target_colors: u32) -> Vec<Rgba<u8>> {
+        let colors: Vec<(Rgba<u8>, u32)> = colors
+            .iter()
+            .copied()
+            .filter(|&(_, weight)| weight > 0)
+            .collect();
+        if colors.is_empty() || target_colors == 0 {
+            return vec![];
+        }
+
+        let mut buckets = vec![colors];
+        while buckets.len() < target_colors as usize {
+            let Some(idx) = buckets
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, bucket)| weighted_largest_range(bucket).1)
+                .map(|(idx, _)| idx)
+            else {
+                break;
+            };
+
+            let mut bucket = buckets.remove(idx);
+            let (axis, range) = weighted_largest_range(&bucket);
+            if bucket.len() <= 1 || range == 0 {
+                buckets.push(bucket);
+                break;
+            }
+
+            bucket.sort_by_key(|(color, _)| color.0[axis]);
+            let half = bucket.iter().map(|&(_, weight)| weight as u64).sum::<u64>() / 2;
+            let mut cumulative = 0u64;
+            let mut mid = 0;
+            while mid < bucket.len() && cumulative < half {
+                cumulative += bucket[mid].1 as u64;
+                mid += 1;
+            }
+            // Keep both halves non-empty when one color carries most of the weight
+            let mid = mid.clamp(1, bucket.len() - 1);
+
+            let right = bucket.split_off(mid);
+            buckets.push(bucket);
+            buckets.push(right);
+        }
+
+        buckets
+            .iter()
+            .map(|bucket| weighted_mean_color(bucket))
+            .collect()
+    }
+
     pub fn find_largest_axis(bucket: &ColorBucket) -> Axis {
         let ranges = bucket.get_color_ranges();
 
@@ -144,6 +198,45 @@ impl MedianCutQuantizer {
     }
 }
 
+// Channel index with the largest spread, preferring red then green on ties
+fn weighted_largest_range(colors: &[(Rgba<u8>, u32)]) -> (usize, u8) {
+    let mut min = [255u8; 3];
+    let mut max = [0u8; 3];
+    for (color, _) in colors {
+        for channel in 0..3 {
+            min[channel] = min[channel].min(color.0[channel]);
+            max[channel] = max[channel].max(color.0[channel]);
+        }
+    }
+
+    let ranges = [0, 1, 2].map(|channel| max[channel].saturating_sub(min[channel]));
+    if ranges[0] >= ranges[1] && ranges[0] >= ranges[2] {
+        (0, ranges[0])
+    } else if ranges[1] >= ranges[2] {
+        (1, ranges[1])
+    } else {
+        (2, ranges[2])
+    }
+}
+
+fn weighted_mean_color(colors: &[(Rgba<u8>, u32)]) -> Rgba<u8> {
+    let mut sums = [0u64; 3];
+    let mut total = 0u64;
+    for (color, weight) in colors {
+        let weight = *weight as u64;
+        for (sum, &value) in sums.iter_mut().zip(&color.0) {
+            *sum += value as u64 * weight;
+        }
+        total += weight;
+    }
+
+    if total == 0 {
+        return Rgba([0, 0, 0, 255]);
+    }
+    let [r, g, b] = sums.map(|sum| (sum / total) as u8);
+    Rgba([r, g, b, 255])
+}
+
 // Fast color quantization with bit manipulation
 #[inline(always)]
 fn quantize_color_15bit(color: Rgba<u8>) -> u16 {
@@ -189,7 +282,7 @@ impl FastMedianCut {
             .collect();
 
         // Sort by frequency (descending)
-        colors_with_counts.sort_by(|a, b| b.1.cmp(&a.1));
+        colors_with_counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
 
         // Take top colors and convert back to RGB
         colors_with_counts
@@ -246,3 +339,176 @@ pub fn analyze_color_frequency(pixels: &[Rgba<u8>]) -> HashMap<Rgba<u8>, u32> {
 
     frequency_map
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantizationMethod {
+    MedianCut,
+    KMeans,
+}
+
+// Pixels closer than this (Lab distance) to a locked color are considered covered by it
+pub const LOCKED_COLOR_RADIUS: f64 = 8.0;
+
+#[derive(Debug, Clone)]
+pub struct PaletteQuantizer {
+    pub method: QuantizationMethod,
+    pub target_colors: u32,
+    pub locked_colors: Vec<Rgba<u8>>,
+    pub max_iterations: u32,
+    pub order: Option<PaletteOrder>,
+}
+
+impl PaletteQuantizer {
+    pub fn new(method: QuantizationMethod, target_colors: u32) -> Self {
+        Self {
+            method,
+            target_colors,
+            locked_colors: Vec::new(),
+            max_iterations: 10,
+            order: None,
+        }
+    }
+
+    // A predefined palette: every color is locked and nothing is derived from the image
+    pub fn fixed(palette: Vec<Rgba<u8>>) -> Self {
+        // Count colors the way they are locked, so duplicates leave no slot to derive into
+        let mut colors: Vec<Rgba<u8>> = Vec::with_capacity(palette.len());
+        for color in palette {
+            let opaque = Rgba([color.0[0], color.0[1], color.0[2], 255]);
+            if !colors.contains(&opaque) {
+                colors.push(opaque);
+            }
+        }
+        Self::new(QuantizationMethod::MedianCut, colors.len() as u32).with_locked_colors(colors)
+    }
+
+    pub fn with_locked_colors(mut self, locked_colors: Vec<Rgba<u8>>) -> Self {
+        self.locked_colors = locked_colors;
+        self
+    }
+
+    pub fn with_order(mut self, order: PaletteOrder) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    // Without an explicit order, locked colors come first followed by derived colors
+    pub fn build_palette(&self, pixels: &[Rgba<u8>]) -> Vec<Rgba<u8>> {
+        let colors: Vec<(Rgba<u8>, u32)> = pixels.iter().map(|&pixel| (pixel, 1)).collect();
+        self.build_palette_weighted(&colors)
+    }
+
+    // Each color counts as `weight` pixels, e.g. a quadtree leaf weighted by its area
+    pub fn build_palette_weighted(&self, colors: &[(Rgba<u8>, u32)]) -> Vec<Rgba<u8>> {
+        let mut palette = self.build_unordered_palette(colors);
+        if let Some(order) = self.order {
+            sort_palette(&mut palette, order);
+        }
+        palette
+    }
+
+    fn build_unordered_palette(&self, colors: &[(Rgba<u8>, u32)]) -> Vec<Rgba<u8>> {
+        let mut palette: Vec<Rgba<u8>> = Vec::new();
+        for color in &self.locked_colors {
+            let opaque = Rgba([color.0[0], color.0[1], color.0[2], 255]);
+            if !palette.contains(&opaque) {
+                palette.push(opaque);
+            }
+        }
+
+        let free_slots = (self.target_colors as usize).saturating_sub(palette.len());
+        if free_slots == 0 || colors.is_empty() {
+            return palette;
+        }
+
+        let uncovered: Vec<(Rgba<u8>, u32)> = colors
+            .iter()
+            .copied()
+            .filter(|(color, _)| {
+                !palette
+                    .iter()
+                    .any(|locked| color_distance_lab_fast(locked, color) < LOCKED_COLOR_RADIUS)
+            })
+            .collect();
+
+        let derived = match self.method {
+            QuantizationMethod::MedianCut => {
+                MedianCutQuantizer::quantize_weighted(&uncovered, free_slots as u32)
+            }
+            QuantizationMethod::KMeans => {
+                let seeds = MedianCutQuantizer::quantize_weighted(&uncovered, free_slots as u32);
+                refine_kmeans(colors, &palette, seeds, self.max_iterations)
+            }
+        };
+
+        for color in derived {
+            if !palette.contains(&color) {
+                palette.push(color);
+            }
+        }
+
+        palette
+    }
+}
+
+// Lloyd iterations where the first `locked.len()` centroids never move
+fn refine_kmeans(
+    colors: &[(Rgba<u8>, u32)],
+    locked: &[Rgba<u8>],
+    seeds: Vec<Rgba<u8>>,
+    max_iterations: u32,
+) -> Vec<Rgba<u8>> {
+    let mut centroids: Vec<Rgba<u8>> = locked.iter().copied().chain(seeds).collect();
+
+    for _ in 0..max_iterations {
+        let mut sums = vec![[0u64; 4]; centroids.len()];
+        for (color, weight) in colors {
+            let idx = nearest_palette_index(color, &centroids);
+            let weight = *weight as u64;
+            sums[idx][0] += color.0[0] as u64 * weight;
+            sums[idx][1] += color.0[1] as u64 * weight;
+            sums[idx][2] += color.0[2] as u64 * weight;
+            sums[idx][3] += weight;
+        }
+
+        let mut changed = false;
+        for (centroid, sum) in centroids.iter_mut().zip(&sums).skip(locked.len()) {
+            if sum[3] == 0 {
+                continue;
+            }
+            let updated = Rgba([
+                (sum[0] / sum[3]) as u8,
+                (sum[1] / sum[3]) as u8,
+                (sum[2] / sum[3]) as u8,
+                255,
+            ]);
+            if updated != *centroid {
+                *centroid = updated;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    centroids.split_off(locked.len())
+}
+
+pub fn nearest_palette_index(color: &Rgba<u8>, palette: &[Rgba<u8>]) -> usize {
+    palette
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| (i, color_distance_lab_fast(color, entry)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+pub fn map_to_palette(color: &Rgba<u8>, palette: &[Rgba<u8>]) -> Rgba<u8> {
+    if palette.is_empty() {
+        return *color;
+    }
+    palette[nearest_palette_index(color, palette)]
+}
@@ -0,0 +1,295 @@
+use crate::core::color::rgba_to_lab_values;
+use crate::core::integral::{IntegralImage, IntegralSpace};
+use crate::core::quadtree::QuadNode;
+use image::RgbaImage;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+// Per-image data shared by split criteria. Tables that only some criteria need are
+// computed on first use, so the context can be shared across rayon workers.
+pub struct SplitContext<'a> {
+    image: &'a RgbaImage,
+    integral: &'a IntegralImage,
+    lab: OnceLock<Vec<[f32; 3]>>,
+    rgb: OnceLock<IntegralImage>,
+    edges: OnceLock<IntegralImage>,
+}
+
+impl<'a> SplitContext<'a> {
+    pub fn new(image: &'a RgbaImage, integral: &'a IntegralImage) -> Self {
+        Self {
+            image,
+            integral,
+            lab: OnceLock::new(),
+            rgb: OnceLock::new(),
+            edges: OnceLock::new(),
+        }
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        self.image
+    }
+
+    pub fn integral(&self) -> &IntegralImage {
+        self.integral
+    }
+
+    fn lab(&self) -> &[[f32; 3]] {
+        self.lab
+            .get_or_init(|| self.image.pixels().map(rgba_to_lab_values).collect())
+    }
+
+    fn region_lab(&self, node: &QuadNode) -> impl Iterator<Item = &[f32; 3]> {
+        let lab = self.lab();
+        let width = self.image.width() as usize;
+        (node.y..node.y + node.height).flat_map(move |y| {
+            let start = y as usize * width + node.x as usize;
+            lab[start..start + node.width as usize].iter()
+        })
+    }
+
+    // Exact test that every pixel has the same RGB value: n * Σx² == (Σx)² per channel.
+    // The RGB table's sums are exact integers, well below 2^53 even for 8K images.
+    pub fn is_uniform(&self, node: &QuadNode) -> bool {
+        let rgb = match self.integral.space() {
+            IntegralSpace::Rgb => self.integral,
+            IntegralSpace::Lab => self
+                .rgb
+                .get_or_init(|| IntegralImage::new(self.image, IntegralSpace::Rgb)),
+        };
+        let (x, y, w, h) = (node.x, node.y, node.width, node.height);
+        let count = w as u128 * h as u128;
+        let sum = rgb.region_sum(x, y, w, h);
+        let square = rgb.region_square_sum(x, y, w, h);
+
+        (0..3).all(|c| count * square[c] as u128 == (sum[c] as u128) * (sum[c] as u128))
+    }
+
+    // Largest Lab distance between any two pixels of the region. Quadratic in the
+    // region's distinct colors; `lab_delta_exceeds` is cheaper for a threshold test.
+    pub fn max_lab_delta(&self, node: &QuadNode) -> f64 {
+        let colors = self.region_colors(node);
+        let bounds = LabBounds::of(&colors);
+        let mut best = bounds.longest_side();
+        for (i, a) in colors.iter().enumerate() {
+            // No partner can be further away than the far corner of the bounding box
+            if bounds.farthest_corner(a) <= best {
+                continue;
+            }
+            for b in &colors[i + 1..] {
+                best = best.max(lab_distance(a, b));
+            }
+        }
+        best
+    }
+
+    // Whether two pixels of the region are more than `threshold` apart in Lab space.
+    // The bounding box settles most regions: its diagonal bounds the largest distance
+    // from above and its longest side from below. Only regions in between are searched.
+    pub fn lab_delta_exceeds(&self, node: &QuadNode, threshold: f64) -> bool {
+        let colors = self.region_colors(node);
+        let bounds = LabBounds::of(&colors);
+        if bounds.diagonal() <= threshold {
+            return false;
+        }
+        if bounds.longest_side() > threshold {
+            return true;
+        }
+        colors.iter().enumerate().any(|(i, a)| {
+            bounds.farthest_corner(a) > threshold
+                && colors[i + 1..]
+                    .iter()
+                    .any(|b| lab_distance(a, b) > threshold)
+        })
+    }
+
+    // Each distinct Lab color of the region once
+    fn region_colors(&self, node: &QuadNode) -> Vec<[f32; 3]> {
+        let mut seen = HashSet::new();
+        self.region_lab(node)
+            .filter(|lab| seen.insert(lab.map(f32::to_bits)))
+            .copied()
+            .collect()
+    }
+
+    // Mean Sobel gradient magnitude of L* over the region
+    pub fn mean_edge_energy(&self, node: &QuadNode) -> f64 {
+        let edges = self.edges.get_or_init(|| {
+            let lab = self.lab();
+            let width = self.image.width();
+            let height = self.image.height();
+            let l = |x: i64, y: i64| {
+                let x = x.clamp(0, width as i64 - 1) as usize;
+                let y = y.clamp(0, height as i64 - 1) as usize;
+                lab[y * width as usize + x][0] as f64
+            };
+
+            IntegralImage::from_fn(width, height, IntegralSpace::Lab, |x, y| {
+                let (x, y) = (x as i64, y as i64);
+                let gx = l(x + 1, y - 1) + 2.0 * l(x + 1, y) + l(x + 1, y + 1)
+                    - l(x - 1, y - 1)
+                    - 2.0 * l(x - 1, y)
+                    - l(x - 1, y + 1);
+                let gy = l(x - 1, y + 1) + 2.0 * l(x, y + 1) + l(x + 1, y + 1)
+                    - l(x - 1, y - 1)
+                    - 2.0 * l(x, y - 1)
+                    - l(x + 1, y - 1);
+                [(gx * gx + gy * gy).sqrt(), 0.0, 0.0]
+            })
+        });
+
+        edges.region_mean(node.x, node.y, node.width, node.height)[0]
+    }
+
+    // Shannon entropy in bits of the region's L* histogram
+    pub fn luminance_entropy(&self, node: &QuadNode, bins: u32) -> f64 {
+        let bins = bins.max(1) as usize;
+        let mut histogram = vec![0u32; bins];
+        let mut total = 0u32;
+        for lab in self.region_lab(node) {
+            let bin = ((lab[0].clamp(0.0, 100.0) / 100.0) * bins as f32) as usize;
+            histogram[bin.min(bins - 1)] += 1;
+            total += 1;
+        }
+
+        histogram
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / total as f64;
+                -p * p.log2()
+            })
+            .sum()
+    }
+}
+
+fn lab_distance(a: &[f32; 3], b: &[f32; 3]) -> f64 {
+    (0..3)
+        .map(|c| ((a[c] - b[c]) as f64).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+struct LabBounds {
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+impl LabBounds {
+    fn of(colors: &[[f32; 3]]) -> Self {
+        let mut bounds = LabBounds {
+            min: [f32::MAX; 3],
+            max: [f32::MIN; 3],
+        };
+        for lab in colors {
+            for (c, &value) in lab.iter().enumerate() {
+                bounds.min[c] = bounds.min[c].min(value);
+                bounds.max[c] = bounds.max[c].max(value);
+            }
+        }
+        bounds
+    }
+
+    fn side(&self, c: usize) -> f64 {
+        (self.max[c] - self.min[c]).max(0.0) as f64
+    }
+
+    fn longest_side(&self) -> f64 {
+        (0..3).map(|c| self.side(c)).fold(0.0, f64::max)
+    }
+
+    fn diagonal(&self) -> f64 {
+        (0..3).map(|c| self.side(c).powi(2)).sum::<f64>().sqrt()
+    }
+
+    fn farthest_corner(&self, lab: &[f32; 3]) -> f64 {
+        (0..3)
+            .map(|c| {
+                let far = (lab[c] - self.min[c]).max(self.max[c] - lab[c]) as f64;
+                far * far
+            })
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+pub trait SplitCriterion: Send + Sync {
+    fn should_split(&self, context: &SplitContext, node: &QuadNode) -> bool;
+
+    fn threshold(&self) -> f64;
+}
+
+// Mean squared Lab distance from the node's mean color
+pub struct VarianceCriterion {
+    pub threshold: f64,
+}
+
+impl SplitCriterion for VarianceCriterion {
+    fn should_split(&self, _context: &SplitContext, node: &QuadNode) -> bool {
+        node.variance > self.threshold
+    }
+
+    fn threshold(&self) -> f64 {
+        self.threshold
+    }
+}
+
+// Splits as soon as any two pixels differ by more than the threshold; catches thin
+// lines that barely move the variance
+pub struct MaxDeltaCriterion {
+    pub threshold: f64,
+}
+
+impl SplitCriterion for MaxDeltaCriterion {
+    fn should_split(&self, context: &SplitContext, node: &QuadNode) -> bool {
+        context.lab_delta_exceeds(node, self.threshold)
+    }
+
+    fn threshold(&self) -> f64 {
+        self.threshold
+    }
+}
+
+pub struct EdgeEnergyCriterion {
+    pub threshold: f64,
+}
+
+impl SplitCriterion for EdgeEnergyCriterion {
+    fn should_split(&self, context: &SplitContext, node: &QuadNode) -> bool {
+        context.mean_edge_energy(node) > self.threshold
+    }
+
+    fn threshold(&self) -> f64 {
+        self.threshold
+    }
+}
+
+// Splits anything that is not a single flat color, so leaves are either uniform or
+// can't be split further
+pub struct UniformityCriterion;
+
+impl SplitCriterion for UniformityCriterion {
+    fn should_split(&self, context: &SplitContext, node: &QuadNode) -> bool {
+        !context.is_uniform(node)
+    }
+
+    fn threshold(&self) -> f64 {
+        0.0
+    }
+}
+
+// Noisy textures with a narrow tonal range have low entropy and stay merged
+pub struct EntropyCriterion {
+    pub threshold: f64,
+    pub bins: u32,
+}
+
+impl SplitCriterion for EntropyCriterion {
+    fn should_split(&self, context: &SplitContext, node: &QuadNode) -> bool {
+        context.luminance_entropy(node, self.bins) > self.threshold
+    }
+
+    fn threshold(&self) -> f64 {
+        self.threshold
+    }
+}
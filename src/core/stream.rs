@@ -0,0 +1,201 @@
+use crate::core::error::{PixelArtError, PixelArtResult};
+use image::RgbaImage;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+// Produces an image top to bottom, one RGBA8 row at a time
+pub trait RowSource {
+    fn dimensions(&self) -> (u32, u32);
+
+    // Fills `row` (width * 4 bytes) with the next row of pixels
+    fn read_row(&mut self, row: &mut [u8]) -> PixelArtResult<()>;
+}
+
+// Consumes an image top to bottom, one RGBA8 row at a time
+pub trait RowSink {
+    fn write_row(&mut self, row: &[u8]) -> PixelArtResult<()>;
+
+    // Flushes anything still buffered once the last row is written
+    fn finish(&mut self) -> PixelArtResult<()> {
+        Ok(())
+    }
+}
+
+// Decodes a PNG row by row without holding the whole image. Interlaced PNGs deliver
+// their rows out of order, so they are rejected.
+pub struct PngRowSource<R: Read> {
+    reader: png::Reader<R>,
+    color_type: png::ColorType,
+}
+
+impl PngRowSource<BufReader<File>> {
+    pub fn open(path: &Path) -> PixelArtResult<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> PngRowSource<R> {
+    pub fn new(input: R) -> PixelArtResult<Self> {
+        let mut decoder = png::Decoder::new(input);
+        // Expands palettes and low bit depths and strips 16-bit samples down to 8
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let reader = decoder.read_info()?;
+        if reader.info().interlaced {
+            return Err(PixelArtError::UnsupportedPixelFormat(
+                "interlaced PNG can't be read row by row".to_string(),
+            ));
+        }
+
+        let (color_type, _) = reader.output_color_type();
+        Ok(Self { reader, color_type })
+    }
+}
+
+impl<R: Read> RowSource for PngRowSource<R> {
+    fn dimensions(&self) -> (u32, u32) {
+        let info = self.reader.info();
+        (info.width, info.height)
+    }
+
+    fn read_row(&mut self, row: &mut [u8]) -> PixelArtResult<()> {
+        let color_type = self.color_type;
+        let data = self
+            .reader
+            .next_row()?
+            .ok_or_else(|| io::Error::new(ErrorKind::UnexpectedEof, "PNG ended early"))?
+            .data();
+
+        let pixels = row.chunks_exact_mut(4);
+        match color_type {
+            png::ColorType::Rgba => row.copy_from_slice(&data[..row.len()]),
+            png::ColorType::Rgb => {
+                for (pixel, rgb) in pixels.zip(data.chunks_exact(3)) {
+                    pixel.copy_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+                }
+            }
+            png::ColorType::GrayscaleAlpha => {
+                for (pixel, ga) in pixels.zip(data.chunks_exact(2)) {
+                    pixel.copy_from_slice(&[ga[0], ga[0], ga[0], ga[1]]);
+                }
+            }
+            png::ColorType::Grayscale => {
+                for (pixel, &gray) in pixels.zip(data) {
+                    pixel.copy_from_slice(&[gray, gray, gray, 255]);
+                }
+            }
+            png::ColorType::Indexed => {
+                return Err(PixelArtError::UnsupportedPixelFormat(
+                    "indexed PNG".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Serves rows from an image already in memory, for formats that can't be decoded
+// incrementally
+pub struct ImageRowSource {
+    image: RgbaImage,
+    next_row: u32,
+}
+
+impl ImageRowSource {
+    pub fn new(image: RgbaImage) -> Self {
+        Self { image, next_row: 0 }
+    }
+}
+
+impl RowSource for ImageRowSource {
+    fn dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn read_row(&mut self, row: &mut [u8]) -> PixelArtResult<()> {
+        let (width, height) = self.image.dimensions();
+        if self.next_row >= height {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "image has no rows left").into());
+        }
+
+        let row_bytes = width as usize * 4;
+        let start = self.next_row as usize * row_bytes;
+        row.copy_from_slice(&self.image.as_raw()[start..start + row_bytes]);
+        self.next_row += 1;
+        Ok(())
+    }
+}
+
+// Encodes rows straight into an RGBA8 PNG
+pub struct PngRowSink<W: Write + 'static> {
+    writer: Option<png::StreamWriter<'static, W>>,
+}
+
+impl PngRowSink<BufWriter<File>> {
+    pub fn create(path: &Path, width: u32, height: u32) -> PixelArtResult<Self> {
+        Self::new(BufWriter::new(File::create(path)?), width, height)
+    }
+}
+
+impl<W: Write + 'static> PngRowSink<W> {
+    pub fn new(output: W, width: u32, height: u32) -> PixelArtResult<Self> {
+        let mut encoder = png::Encoder::new(output, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let writer = encoder.write_header()?.into_stream_writer()?;
+        Ok(Self {
+            writer: Some(writer),
+        })
+    }
+}
+
+impl<W: Write + 'static> RowSink for PngRowSink<W> {
+    fn write_row(&mut self, row: &[u8]) -> PixelArtResult<()> {
+        let writer = self
+            .writer
+            .as_mut()
+            .ok_or_else(|| io::Error::other("PNG was already finished"))?;
+        writer.write_all(row)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> PixelArtResult<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+// Collects rows into an image in memory
+pub struct ImageRowSink {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl ImageRowSink {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: Vec::with_capacity(width as usize * height as usize * 4),
+        }
+    }
+
+    // None until every row has been written
+    pub fn into_image(self) -> Option<RgbaImage> {
+        RgbaImage::from_raw(self.width, self.height, self.data)
+    }
+}
+
+impl RowSink for ImageRowSink {
+    fn write_row(&mut self, row: &[u8]) -> PixelArtResult<()> {
+        if row.len() != self.width as usize * 4 {
+            let message = format!("row has {} bytes, expected {}", row.len(), self.width * 4);
+            return Err(io::Error::new(ErrorKind::InvalidInput, message).into());
+        }
+        self.data.extend_from_slice(row);
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use image::{Rgba, RgbaImage};
+
+// A rectangle of pixels borrowed from a larger RGBA8 buffer. Each row is contiguous and
+// consecutive rows start `stride` pixels apart, so nothing is copied.
+#[derive(Debug, Clone, Copy)]
+pub struct PixelView<'a> {
+    pixels: &'a [Rgba<u8>],
+    stride: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> PixelView<'a> {
+    // A single row over `pixels`
+    pub fn from_slice(pixels: &'a [Rgba<u8>]) -> Self {
+        Self {
+            pixels,
+            stride: pixels.len(),
+            width: pixels.len(),
+            height: usize::from(!pixels.is_empty()),
+        }
+    }
+
+    // The part of the given rectangle that lies inside `image`
+    pub fn from_image(image: &'a RgbaImage, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let (image_width, image_height) = image.dimensions();
+        let x = x.min(image_width);
+        let y = y.min(image_height);
+        let width = width.min(image_width - x) as usize;
+        let height = height.min(image_height - y) as usize;
+        let stride = image_width as usize;
+
+        if width == 0 || height == 0 {
+            return Self::from_slice(&[]);
+        }
+
+        let start = y as usize * stride + x as usize;
+        let end = start + (height - 1) * stride + width;
+        Self {
+            pixels: &as_pixels(image.as_raw())[start..end],
+            stride,
+            width,
+            height,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn row(&self, row: usize) -> &'a [Rgba<u8>] {
+        let start = row * self.stride;
+        &self.pixels[start..start + self.width]
+    }
+
+    pub fn rows(self) -> impl Iterator<Item = &'a [Rgba<u8>]> {
+        (0..self.height).map(move |row| self.row(row))
+    }
+
+    pub fn pixels(self) -> impl Iterator<Item = &'a Rgba<u8>> {
+        self.rows().flatten()
+    }
+
+    // The pixels as one slice when the rows are adjacent in memory
+    pub fn as_slice(&self) -> Option<&'a [Rgba<u8>]> {
+        (self.height <= 1 || self.width == self.stride).then(|| &self.pixels[..self.len()])
+    }
+
+    pub fn to_vec(&self) -> Vec<Rgba<u8>> {
+        let mut pixels = Vec::with_capacity(self.len());
+        for row in self.rows() {
+            pixels.extend_from_slice(row);
+        }
+        pixels
+    }
+}
+
+fn as_pixels(raw: &[u8]) -> &[Rgba<u8>] {
+    // Rgba<u8> is repr(transparent) over [u8; 4]: size 4, alignment 1
+    unsafe { std::slice::from_raw_parts(raw.as_ptr() as *const Rgba<u8>, raw.len() / 4) }
+}
@@ -1,69 +1,417 @@
 use anyhow::{Context, Result};
 use clap::Parser;
-use pixel_art_rust::cli::args::{Args, ColorAlgorithm};
-use pixel_art_rust::cli::visualizer::GridVisualizer;
+use image::{DynamicImage, Rgba};
+use pixel_art_rust::cli::args::{Args, ColorAlgorithm, LayoutMode, OverlayBase, SplitMode};
+use pixel_art_rust::cli::visualizer::{GridVisualizer, TreeVisualizer};
 use pixel_art_rust::core::color::{AverageColorExtractor, KMeansExtractor, MedianCutExtractor};
-use pixel_art_rust::core::grid::Grid;
-use pixel_art_rust::core::pixel_art::PixelArtConverter;
-use std::sync::Arc;
+use pixel_art_rust::core::detect::GridDetector;
+use pixel_art_rust::core::grid::{Grid, GridSpec, Rect, grid_region};
+use pixel_art_rust::core::layout::{GridLayout, TiledGrid};
+use pixel_art_rust::core::palette::{
+    RampGenerator, merge_near_duplicates, palette_to_gpl, palette_to_hex, write_indexed_png,
+};
+use pixel_art_rust::core::pixel_art::{PixelArtConverter, PixelArtConverterBuilder};
+use pixel_art_rust::core::quadtree::{QuadTree, QuadTreeConfig};
+use pixel_art_rust::core::quantization::{FastMedianCut, PaletteQuantizer, QuantizationMethod};
+use pixel_art_rust::core::split::{
+    EdgeEnergyCriterion, EntropyCriterion, MaxDeltaCriterion, SplitCriterion, VarianceCriterion,
+};
+use pixel_art_rust::core::stream::{ImageRowSource, PngRowSink, PngRowSource, RowSource};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+enum Visualizer {
+    Grid(Arc<GridVisualizer>),
+    Tree(Arc<TreeVisualizer>),
+}
 
 fn main() -> Result<()> {
     let args = Args::parse();
     args.validate().context("Invalid arguments")?;
 
+    if args.stream {
+        return run_streaming(&args);
+    }
+
     println!("Loading image: {:?}", args.input);
     let image = image::open(&args.input)
         .with_context(|| format!("Failed to open image: {:?}", args.input))?;
 
     println!("Image loaded: {}x{}", image.width(), image.height());
 
-    let visualizer = if args.adaptive {
-        // For quadtree, we don't know the exact grid size, so use a reasonable default
-        Arc::new(GridVisualizer::new(args.height, args.width))
+    let (image, placement) = if args.detect_grid {
+        detect_grid(image)?
     } else {
-        Arc::new(GridVisualizer::new(args.height, args.width))
+        (image, Placement::from_args(&args))
     };
 
-    let mut converter = if args.adaptive {
+    // Grid mode always has a grid; adaptive mode only uses one with --lattice
+    let (frame, area) = placement.region(image.width(), image.height())?;
+    let grid = placement
+        .spec
+        .as_ref()
+        .map(|spec| spec.resolve_with_aspect(area.width, area.height, args.par))
+        .transpose()
+        .context("Invalid grid size")?;
+
+    let mut builder = if args.adaptive {
+        let mut config = create_quadtree_config(&args);
+        if args.lattice
+            && let Some(grid) = &grid
+        {
+            config = config.with_cell_size(grid.cell_width(), grid.cell_height());
+        }
         println!(
-            "Using adaptive quadtree processing (depth: {}, threshold: {})",
-            args.max_depth, args.variance_threshold
-        );
-        PixelArtConverter::with_quadtree(
+            "Using adaptive quadtree processing (depth: {}, criterion: {:?}, threshold: {})",
             args.max_depth,
-            args.variance_threshold,
-            create_color_extractor(&args)?,
+            args.split_criterion,
+            config.criterion.threshold()
+        );
+        placement.apply(
+            PixelArtConverter::builder()
+                .quadtree(config)
+                .boxed_extractor(create_color_extractor(&args)?),
         )
     } else {
+        let grid = grid.as_ref().context("No grid size given")?;
+        print_grid(grid, args.layout.into(), "");
+        grid_converter_builder(&args, &placement)?
+    };
+
+    if let Some(quantizer) = create_palette_quantizer(&args, Some(&image)) {
         println!(
-            "Using uniform grid processing ({}x{})",
-            args.width, args.height
+            "Quantizing to {} colors ({} locked)",
+            quantizer.target_colors,
+            quantizer.locked_colors.len()
         );
-        let grid = Grid::new(image.width(), image.height(), args.width, args.height);
-        PixelArtConverter::with_grid(grid, create_color_extractor(&args)?)
-    };
+        builder = builder.palette(quantizer);
+    }
+    let palette = Arc::new(Mutex::new(Vec::new()));
+    builder = record_palette(builder, &palette);
 
-    // Clone visualizer Arc for the callback
-    let vis_callback = Arc::clone(&visualizer);
-    converter.set_progress_callback(Arc::new(move |row, col| {
-        vis_callback.update_cell(row, col);
-    }));
+    let visualizer = if args.adaptive {
+        let visualizer = Arc::new(TreeVisualizer::new(area.width as u64 * area.height as u64));
+        let vis_callback = Arc::clone(&visualizer);
+        builder = builder.tree_progress_callback(Arc::new(move |done, total| {
+            vis_callback.update(done, total);
+        }));
+        Visualizer::Tree(visualizer)
+    } else {
+        // Staggered layouts number their cells past the grid's rows and columns
+        let tiles = TiledGrid::new(
+            args.layout.into(),
+            grid.clone().context("No grid size given")?,
+        );
+        let visualizer = Arc::new(GridVisualizer::new(
+            tiles.index_rows(),
+            tiles.index_columns(),
+        ));
+        let vis_callback = Arc::clone(&visualizer);
+        builder = builder.progress_callback(Arc::new(move |row, col| {
+            vis_callback.update_cell(row, col);
+        }));
+        Visualizer::Grid(visualizer)
+    };
+    let converter = builder.build().context("Invalid converter settings")?;
 
     println!("Converting image to pixel art...");
-    let pixel_art = converter
-        .convert_parallel(&image)
+    let (pixel_art, tree) = converter
+        .convert_parallel_with_tree(&image)
         .context("Failed to convert image")?;
 
     println!("Saving result to: {:?}", args.output);
-    pixel_art
-        .save(&args.output)
-        .with_context(|| format!("Failed to save image: {:?}", args.output))?;
+    let palette = palette.lock().unwrap().clone();
+    if args.indexed {
+        let output = File::create(&args.output)
+            .with_context(|| format!("Failed to create output: {:?}", args.output))?;
+        write_indexed_png(BufWriter::new(output), &pixel_art.to_rgba8(), &palette)
+            .with_context(|| format!("Failed to save image: {:?}", args.output))?;
+    } else {
+        pixel_art
+            .save(&args.output)
+            .with_context(|| format!("Failed to save image: {:?}", args.output))?;
+    }
+    if let Some(path) = &args.palette_out {
+        write_palette(path, &palette)?;
+    }
 
+    match &visualizer {
+        Visualizer::Grid(visualizer) => visualizer.finish(),
+        Visualizer::Tree(visualizer) => visualizer.finish(),
+    }
+
+    if let Some(tree) = &tree {
+        // Tree nodes are relative to the grid area, which sits at the offset in the output
+        let overlay_base = match args.overlay_base {
+            OverlayBase::Source => image.crop_imm(area.x, area.y, area.width, area.height),
+            OverlayBase::Result => {
+                pixel_art.crop_imm(area.x - frame.x, area.y - frame.y, area.width, area.height)
+            }
+        };
+        write_quadtree_debug(&args, tree, &overlay_base)?;
+    }
+
+    println!("Conversion completed successfully!");
+    Ok(())
+}
+
+fn run_streaming(args: &Args) -> Result<()> {
+    println!("Streaming image: {:?}", args.input);
+    let mut source = open_row_source(&args.input)?;
+    let (width, height) = source.dimensions();
+    println!("Image size: {}x{}", width, height);
+
+    let placement = Placement::from_args(args);
+    let (_, area) = placement.region(width, height)?;
+    let grid = placement
+        .spec
+        .as_ref()
+        .context("No grid size given")?
+        .resolve_with_aspect(area.width, area.height, args.par)
+        .context("Invalid grid size")?;
+    print_grid(&grid, GridLayout::Rectangular, " in bands of cell rows");
+    let mut builder = grid_converter_builder(args, &placement)?;
+    if let Some(quantizer) = create_palette_quantizer(args, None) {
+        println!(
+            "Quantizing to {} colors ({} locked)",
+            quantizer.target_colors,
+            quantizer.locked_colors.len()
+        );
+        builder = builder.palette(quantizer);
+    }
+    let palette = Arc::new(Mutex::new(Vec::new()));
+    builder = record_palette(builder, &palette);
+
+    let visualizer = Arc::new(GridVisualizer::new(grid.height(), grid.width()));
+    let vis_callback = Arc::clone(&visualizer);
+    let converter = builder
+        .progress_callback(Arc::new(move |row, col| {
+            vis_callback.update_cell(row, col);
+        }))
+        .build()
+        .context("Invalid converter settings")?;
+    let (out_width, out_height) = converter.output_dimensions(width, height)?;
+
+    println!(
+        "Converting image to pixel art, writing to: {:?}",
+        args.output
+    );
+    let mut sink = PngRowSink::create(&args.output, out_width, out_height)
+        .with_context(|| format!("Failed to create output: {:?}", args.output))?;
+    converter
+        .convert_streaming(source.as_mut(), &mut sink)
+        .context("Failed to convert image")?;
     visualizer.finish();
+    if let Some(path) = &args.palette_out {
+        write_palette(path, &palette.lock().unwrap())?;
+    }
+
     println!("Conversion completed successfully!");
     Ok(())
 }
 
+// Keeps the palette the conversion used, for --palette-out and --indexed
+fn record_palette(
+    builder: PixelArtConverterBuilder,
+    palette: &Arc<Mutex<Vec<Rgba<u8>>>>,
+) -> PixelArtConverterBuilder {
+    let palette = Arc::clone(palette);
+    builder.palette_callback(Arc::new(move |colors| {
+        *palette.lock().unwrap() = colors.to_vec();
+    }))
+}
+
+fn write_palette(path: &Path, palette: &[Rgba<u8>]) -> Result<()> {
+    println!("Writing {} palette colors to: {:?}", palette.len(), path);
+    let is_gpl = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gpl"));
+    let text = if is_gpl {
+        let name = path
+            .file_stem()
+            .map_or("pixel-art-rust".into(), |stem| stem.to_string_lossy());
+        palette_to_gpl(palette, &name)
+    } else {
+        palette_to_hex(palette)
+    };
+    std::fs::write(path, text).with_context(|| format!("Failed to write palette: {path:?}"))
+}
+
+fn print_grid(grid: &Grid, layout: GridLayout, suffix: &str) {
+    let shape = match layout {
+        GridLayout::Rectangular => String::new(),
+        layout => format!(", {layout:?} layout"),
+    };
+    println!(
+        "Using uniform grid processing ({}x{} cells of {}x{} pixels{}){}",
+        grid.width(),
+        grid.height(),
+        grid.cell_width(),
+        grid.cell_height(),
+        shape,
+        suffix
+    );
+}
+
+// Where the grid goes: from the sizing options, --crop and --offset-x/-y, or from
+// --detect-grid
+struct Placement {
+    spec: Option<GridSpec>,
+    crop: Option<Rect>,
+    offset: (u32, u32),
+    detected: bool,
+}
+
+impl Placement {
+    fn from_args(args: &Args) -> Self {
+        Self {
+            spec: args.grid_spec(),
+            crop: args.crop,
+            offset: (args.offset_x, args.offset_y),
+            detected: false,
+        }
+    }
+
+    // The output frame and the area the grid covers
+    fn region(&self, width: u32, height: u32) -> Result<(Rect, Rect)> {
+        grid_region(width, height, self.crop, self.offset).context("Invalid crop or grid offset")
+    }
+
+    fn apply(&self, mut builder: PixelArtConverterBuilder) -> PixelArtConverterBuilder {
+        if let Some(crop) = self.crop {
+            builder = builder.crop(crop);
+        }
+        builder.offset(self.offset.0, self.offset.1)
+    }
+}
+
+// Finds the grid of upscaled pixel art. With a non-integer scale factor the image is
+// resampled so that cells are whole pixels.
+fn detect_grid(image: DynamicImage) -> Result<(DynamicImage, Placement)> {
+    let detected = GridDetector::new()
+        .detect(&image.to_rgba8())
+        .context("Failed to detect the pixel grid")?;
+    println!(
+        "Detected grid: {}x{} cells of {:.2}x{:.2} pixels, offset ({:.2}, {:.2}), confidence {:.2}",
+        detected.columns,
+        detected.rows,
+        detected.cell_width,
+        detected.cell_height,
+        detected.offset_x,
+        detected.offset_y,
+        detected.confidence
+    );
+
+    let (image, detected) = if detected.is_integral() {
+        (image, detected)
+    } else {
+        let (image, detected) = detected.resample(&image);
+        println!(
+            "Resampled to {}x{} for whole {}x{} pixel cells",
+            image.width(),
+            image.height(),
+            detected.cell_width,
+            detected.cell_height
+        );
+        (image, detected)
+    };
+
+    let placement = Placement {
+        spec: Some(detected.spec()),
+        crop: None,
+        offset: detected.offset(),
+        detected: true,
+    };
+    Ok((image, placement))
+}
+
+// Grid-mode settings shared by regular and streaming conversion
+fn grid_converter_builder(args: &Args, placement: &Placement) -> Result<PixelArtConverterBuilder> {
+    let mut builder = PixelArtConverter::builder()
+        .grid(placement.spec.clone().context("No grid size given")?)
+        .boxed_extractor(create_color_extractor(args)?)
+        .merge_uniform(args.merge_uniform)
+        .dithering(args.dither.into())
+        .layout(args.layout.into())
+        .pixel_aspect(args.par);
+    // A detected grid recovers the native sprite unless another scale is asked for, or
+    // the cells are painted in another shape
+    match args.scale {
+        Some(scale) => builder = builder.scale(scale),
+        None if placement.detected && args.layout == LayoutMode::Rect => builder = builder.scale(1),
+        None => {}
+    }
+    Ok(placement.apply(builder))
+}
+
+// PNG input is decoded row by row; anything else has to be decoded whole first
+fn open_row_source(path: &Path) -> Result<Box<dyn RowSource>> {
+    let is_png = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    if is_png {
+        match PngRowSource::open(path) {
+            Ok(source) => return Ok(Box::new(source)),
+            Err(err) => println!("Cannot stream {:?} ({}), decoding it whole", path, err),
+        }
+    } else {
+        println!(
+            "Only PNG input is read row by row, decoding {:?} whole",
+            path
+        );
+    }
+
+    let image = image::open(path).with_context(|| format!("Failed to open image: {:?}", path))?;
+    Ok(Box::new(ImageRowSource::new(image.to_rgba8())))
+}
+
+// `overlay_base` is the source or the result, cropped to the area the tree covers
+fn write_quadtree_debug(args: &Args, tree: &QuadTree, overlay_base: &DynamicImage) -> Result<()> {
+    if let Some(path) = &args.tree_json {
+        println!(
+            "Writing quadtree ({} nodes) to: {:?}",
+            tree.node_count(),
+            path
+        );
+        std::fs::write(path, tree.to_json()?)
+            .with_context(|| format!("Failed to write quadtree JSON: {path:?}"))?;
+    }
+
+    if let Some(path) = &args.overlay {
+        println!("Saving quadtree overlay to: {:?}", path);
+        tree.render_overlay(&overlay_base.to_rgba8())
+            .save(path)
+            .with_context(|| format!("Failed to save overlay: {path:?}"))?;
+    }
+
+    Ok(())
+}
+
+fn create_quadtree_config(args: &Args) -> QuadTreeConfig {
+    let criterion: Arc<dyn SplitCriterion> = match args.split_criterion {
+        SplitMode::Variance => Arc::new(VarianceCriterion {
+            threshold: args.split_threshold.unwrap_or(args.variance_threshold),
+        }),
+        SplitMode::MaxDelta => Arc::new(MaxDeltaCriterion {
+            threshold: args.split_threshold.unwrap_or(30.0),
+        }),
+        SplitMode::Edge => Arc::new(EdgeEnergyCriterion {
+            threshold: args.split_threshold.unwrap_or(10.0),
+        }),
+        SplitMode::Entropy => Arc::new(EntropyCriterion {
+            threshold: args.split_threshold.unwrap_or(2.0),
+            bins: 16,
+        }),
+    };
+
+    QuadTreeConfig::new(args.max_depth, args.variance_threshold)
+        .with_criterion(criterion)
+        .with_min_node_size(args.min_node_size)
+        .with_balance(args.balance)
+}
+
 fn create_color_extractor(
     args: &Args,
 ) -> Result<Box<dyn pixel_art_rust::core::color::ColorExtractor>> {
@@ -82,3 +430,47 @@ fn create_color_extractor(
         }
     }
 }
+
+// Ramps are built from the image's dominant colors; without the image they are skipped
+fn create_palette_quantizer(args: &Args, image: Option<&DynamicImage>) -> Option<PaletteQuantizer> {
+    if !args.uses_palette() {
+        return None;
+    }
+
+    if let (Some(steps), Some(image)) = (args.ramps, image) {
+        let palette_size = args.colors.unwrap_or(16) as usize;
+        let base_count = ((palette_size - args.lock_colors.len()) / steps as usize).max(1);
+
+        // Oversample dominant colors so near-identical bases can be dropped
+        let pixels: Vec<Rgba<u8>> = image.to_rgba8().pixels().copied().collect();
+        let mut histogram = FastMedianCut::new();
+        histogram.build_histogram(&pixels);
+        let bases: Vec<Rgba<u8>> =
+            merge_near_duplicates(&histogram.get_dominant_colors(base_count * 8), 15.0)
+                .into_iter()
+                .take(base_count)
+                .collect();
+
+        let generator = RampGenerator::new(steps).with_hue_shift(args.hue_shift);
+        let mut palette = args.lock_colors.clone();
+        palette.extend(generator.palette(&bases));
+        return Some(with_palette_order(args, PaletteQuantizer::fixed(palette)));
+    }
+
+    let method = match args.algorithm {
+        ColorAlgorithm::KMeans => QuantizationMethod::KMeans,
+        ColorAlgorithm::Average | ColorAlgorithm::MedianCut => QuantizationMethod::MedianCut,
+    };
+    let target_colors = args.colors.unwrap_or(16);
+
+    let quantizer =
+        PaletteQuantizer::new(method, target_colors).with_locked_colors(args.lock_colors.clone());
+    Some(with_palette_order(args, quantizer))
+}
+
+fn with_palette_order(args: &Args, quantizer: PaletteQuantizer) -> PaletteQuantizer {
+    match args.palette_order {
+        Some(order) => quantizer.with_order(order.into()),
+        None => quantizer,
+    }
+}
@@ -1,5 +1,7 @@
 use clap::Parser;
+use image::Rgba;
 use pixel_art_rust::cli::args::*;
+use pixel_art_rust::core::grid::PixelAspect;
 use std::path::PathBuf;
 
 #[test]
@@ -21,8 +23,8 @@ fn test_args_parsing_valid_input() {
     assert!(parsed.is_ok());
     let args = parsed.unwrap();
 
-    assert_eq!(args.width, 32);
-    assert_eq!(args.height, 24);
+    assert_eq!(args.width, Some(32));
+    assert_eq!(args.height, Some(24));
     assert_eq!(args.input, PathBuf::from("input.jpg"));
     assert_eq!(args.output, PathBuf::from("output.png"));
     assert_eq!(args.algorithm, ColorAlgorithm::Average);
@@ -60,8 +62,8 @@ fn test_args_parsing_with_all_options() {
     assert!(parsed.is_ok());
     let args = parsed.unwrap();
 
-    assert_eq!(args.width, 64);
-    assert_eq!(args.height, 48);
+    assert_eq!(args.width, Some(64));
+    assert_eq!(args.height, Some(48));
     assert_eq!(args.input, PathBuf::from("test.png"));
     assert_eq!(args.output, PathBuf::from("result.jpg"));
     assert_eq!(args.algorithm, ColorAlgorithm::KMeans);
@@ -74,30 +76,86 @@ fn test_args_parsing_with_all_options() {
 #[test]
 fn test_args_validation_invalid_dimensions() {
     let args = Args {
-        width: 0,
-        height: 32,
+        width: Some(0),
+        height: Some(32),
+        cell_size: None,
+        target_width: None,
+        default_size: false,
+        detect_grid: false,
         input: PathBuf::from("test.jpg"),
         output: PathBuf::from("out.png"),
         algorithm: ColorAlgorithm::Average,
         colors: None,
+        quantize: false,
+        lock_colors: vec![],
+        ramps: None,
+        hue_shift: 20.0,
+        palette_order: None,
+        palette_out: None,
+        indexed: false,
         adaptive: false,
         max_depth: 10,
         variance_threshold: 50.0,
+        split_criterion: SplitMode::Variance,
+        split_threshold: None,
+        min_node_size: 1,
+        lattice: false,
+        balance: false,
+        merge_uniform: false,
+        dither: DitherMode::None,
+        scale: None,
+        stream: false,
+        layout: LayoutMode::Rect,
+        par: PixelAspect::SQUARE,
+        offset_x: 0,
+        offset_y: 0,
+        crop: None,
+        tree_json: None,
+        overlay: None,
+        overlay_base: OverlayBase::Result,
     };
 
     let result = args.validate();
     assert!(result.is_err());
 
     let args = Args {
-        width: 32,
-        height: 0,
+        width: Some(32),
+        height: Some(0),
+        cell_size: None,
+        target_width: None,
+        default_size: false,
+        detect_grid: false,
         input: PathBuf::from("test.jpg"),
         output: PathBuf::from("out.png"),
         algorithm: ColorAlgorithm::Average,
         colors: None,
+        quantize: false,
+        lock_colors: vec![],
+        ramps: None,
+        hue_shift: 20.0,
+        palette_order: None,
+        palette_out: None,
+        indexed: false,
         adaptive: false,
         max_depth: 10,
         variance_threshold: 50.0,
+        split_criterion: SplitMode::Variance,
+        split_threshold: None,
+        min_node_size: 1,
+        lattice: false,
+        balance: false,
+        merge_uniform: false,
+        dither: DitherMode::None,
+        scale: None,
+        stream: false,
+        layout: LayoutMode::Rect,
+        par: PixelAspect::SQUARE,
+        offset_x: 0,
+        offset_y: 0,
+        crop: None,
+        tree_json: None,
+        overlay: None,
+        overlay_base: OverlayBase::Result,
     };
 
     let result = args.validate();
@@ -107,30 +165,86 @@ fn test_args_validation_invalid_dimensions() {
 #[test]
 fn test_args_validation_invalid_colors() {
     let args = Args {
-        width: 32,
-        height: 32,
+        width: Some(32),
+        height: Some(32),
+        cell_size: None,
+        target_width: None,
+        default_size: false,
+        detect_grid: false,
         input: PathBuf::from("test.jpg"),
         output: PathBuf::from("out.png"),
         algorithm: ColorAlgorithm::KMeans,
         colors: Some(0),
+        quantize: false,
+        lock_colors: vec![],
+        ramps: None,
+        hue_shift: 20.0,
+        palette_order: None,
+        palette_out: None,
+        indexed: false,
         adaptive: false,
         max_depth: 10,
         variance_threshold: 50.0,
+        split_criterion: SplitMode::Variance,
+        split_threshold: None,
+        min_node_size: 1,
+        lattice: false,
+        balance: false,
+        merge_uniform: false,
+        dither: DitherMode::None,
+        scale: None,
+        stream: false,
+        layout: LayoutMode::Rect,
+        par: PixelAspect::SQUARE,
+        offset_x: 0,
+        offset_y: 0,
+        crop: None,
+        tree_json: None,
+        overlay: None,
+        overlay_base: OverlayBase::Result,
     };
 
     let result = args.validate();
     assert!(result.is_err());
 
     let args = Args {
-        width: 32,
-        height: 32,
+        width: Some(32),
+        height: Some(32),
+        cell_size: None,
+        target_width: None,
+        default_size: false,
+        detect_grid: false,
         input: PathBuf::from("test.jpg"),
         output: PathBuf::from("out.png"),
         algorithm: ColorAlgorithm::KMeans,
         colors: Some(257),
+        quantize: false,
+        lock_colors: vec![],
+        ramps: None,
+        hue_shift: 20.0,
+        palette_order: None,
+        palette_out: None,
+        indexed: false,
         adaptive: false,
         max_depth: 10,
         variance_threshold: 50.0,
+        split_criterion: SplitMode::Variance,
+        split_threshold: None,
+        min_node_size: 1,
+        lattice: false,
+        balance: false,
+        merge_uniform: false,
+        dither: DitherMode::None,
+        scale: None,
+        stream: false,
+        layout: LayoutMode::Rect,
+        par: PixelAspect::SQUARE,
+        offset_x: 0,
+        offset_y: 0,
+        crop: None,
+        tree_json: None,
+        overlay: None,
+        overlay_base: OverlayBase::Result,
     };
 
     let result = args.validate();
@@ -140,15 +254,43 @@ fn test_args_validation_invalid_colors() {
 #[test]
 fn test_args_file_path_validation() {
     let args = Args {
-        width: 32,
-        height: 32,
+        width: Some(32),
+        height: Some(32),
+        cell_size: None,
+        target_width: None,
+        default_size: false,
+        detect_grid: false,
         input: PathBuf::from(""),
         output: PathBuf::from("out.png"),
         algorithm: ColorAlgorithm::Average,
         colors: None,
+        quantize: false,
+        lock_colors: vec![],
+        ramps: None,
+        hue_shift: 20.0,
+        palette_order: None,
+        palette_out: None,
+        indexed: false,
         adaptive: false,
         max_depth: 10,
         variance_threshold: 50.0,
+        split_criterion: SplitMode::Variance,
+        split_threshold: None,
+        min_node_size: 1,
+        lattice: false,
+        balance: false,
+        merge_uniform: false,
+        dither: DitherMode::None,
+        scale: None,
+        stream: false,
+        layout: LayoutMode::Rect,
+        par: PixelAspect::SQUARE,
+        offset_x: 0,
+        offset_y: 0,
+        crop: None,
+        tree_json: None,
+        overlay: None,
+        overlay_base: OverlayBase::Result,
     };
 
     let result = args.validate();
@@ -272,15 +414,43 @@ fn test_missing_required_args() {
 #[test]
 fn test_validation_valid_args() {
     let args = Args {
-        width: 32,
-        height: 32,
+        width: Some(32),
+        height: Some(32),
+        cell_size: None,
+        target_width: None,
+        default_size: false,
+        detect_grid: false,
         input: PathBuf::from("test.jpg"),
         output: PathBuf::from("out.png"),
         algorithm: ColorAlgorithm::Average,
         colors: None,
+        quantize: false,
+        lock_colors: vec![],
+        ramps: None,
+        hue_shift: 20.0,
+        palette_order: None,
+        palette_out: None,
+        indexed: false,
         adaptive: false,
         max_depth: 10,
         variance_threshold: 50.0,
+        split_criterion: SplitMode::Variance,
+        split_threshold: None,
+        min_node_size: 1,
+        lattice: false,
+        balance: false,
+        merge_uniform: false,
+        dither: DitherMode::None,
+        scale: None,
+        stream: false,
+        layout: LayoutMode::Rect,
+        par: PixelAspect::SQUARE,
+        offset_x: 0,
+        offset_y: 0,
+        crop: None,
+        tree_json: None,
+        overlay: None,
+        overlay_base: OverlayBase::Result,
     };
 
     let result = args.validate();
@@ -290,30 +460,86 @@ fn test_validation_valid_args() {
 #[test]
 fn test_validation_max_depth_constraints() {
     let args = Args {
-        width: 32,
-        height: 32,
+        width: Some(32),
+        height: Some(32),
+        cell_size: None,
+        target_width: None,
+        default_size: false,
+        detect_grid: false,
         input: PathBuf::from("test.jpg"),
         output: PathBuf::from("out.png"),
         algorithm: ColorAlgorithm::Average,
         colors: None,
+        quantize: false,
+        lock_colors: vec![],
+        ramps: None,
+        hue_shift: 20.0,
+        palette_order: None,
+        palette_out: None,
+        indexed: false,
         adaptive: true,
         max_depth: 0,
         variance_threshold: 50.0,
+        split_criterion: SplitMode::Variance,
+        split_threshold: None,
+        min_node_size: 1,
+        lattice: false,
+        balance: false,
+        merge_uniform: false,
+        dither: DitherMode::None,
+        scale: None,
+        stream: false,
+        layout: LayoutMode::Rect,
+        par: PixelAspect::SQUARE,
+        offset_x: 0,
+        offset_y: 0,
+        crop: None,
+        tree_json: None,
+        overlay: None,
+        overlay_base: OverlayBase::Result,
     };
 
     let result = args.validate();
     assert!(result.is_err());
 
     let args = Args {
-        width: 32,
-        height: 32,
+        width: Some(32),
+        height: Some(32),
+        cell_size: None,
+        target_width: None,
+        default_size: false,
+        detect_grid: false,
         input: PathBuf::from("test.jpg"),
         output: PathBuf::from("out.png"),
         algorithm: ColorAlgorithm::Average,
         colors: None,
+        quantize: false,
+        lock_colors: vec![],
+        ramps: None,
+        hue_shift: 20.0,
+        palette_order: None,
+        palette_out: None,
+        indexed: false,
         adaptive: true,
         max_depth: 21,
         variance_threshold: 50.0,
+        split_criterion: SplitMode::Variance,
+        split_threshold: None,
+        min_node_size: 1,
+        lattice: false,
+        balance: false,
+        merge_uniform: false,
+        dither: DitherMode::None,
+        scale: None,
+        stream: false,
+        layout: LayoutMode::Rect,
+        par: PixelAspect::SQUARE,
+        offset_x: 0,
+        offset_y: 0,
+        crop: None,
+        tree_json: None,
+        overlay: None,
+        overlay_base: OverlayBase::Result,
     };
 
     let result = args.validate();
@@ -323,32 +549,611 @@ fn test_validation_max_depth_constraints() {
 #[test]
 fn test_validation_variance_threshold_constraints() {
     let args = Args {
-        width: 32,
-        height: 32,
+        width: Some(32),
+        height: Some(32),
+        cell_size: None,
+        target_width: None,
+        default_size: false,
+        detect_grid: false,
         input: PathBuf::from("test.jpg"),
         output: PathBuf::from("out.png"),
         algorithm: ColorAlgorithm::Average,
         colors: None,
+        quantize: false,
+        lock_colors: vec![],
+        ramps: None,
+        hue_shift: 20.0,
+        palette_order: None,
+        palette_out: None,
+        indexed: false,
         adaptive: true,
         max_depth: 10,
         variance_threshold: -1.0,
+        split_criterion: SplitMode::Variance,
+        split_threshold: None,
+        min_node_size: 1,
+        lattice: false,
+        balance: false,
+        merge_uniform: false,
+        dither: DitherMode::None,
+        scale: None,
+        stream: false,
+        layout: LayoutMode::Rect,
+        par: PixelAspect::SQUARE,
+        offset_x: 0,
+        offset_y: 0,
+        crop: None,
+        tree_json: None,
+        overlay: None,
+        overlay_base: OverlayBase::Result,
     };
 
     let result = args.validate();
     assert!(result.is_err());
 
     let args = Args {
-        width: 32,
-        height: 32,
+        width: Some(32),
+        height: Some(32),
+        cell_size: None,
+        target_width: None,
+        default_size: false,
+        detect_grid: false,
         input: PathBuf::from("test.jpg"),
         output: PathBuf::from("out.png"),
         algorithm: ColorAlgorithm::Average,
         colors: None,
+        quantize: false,
+        lock_colors: vec![],
+        ramps: None,
+        hue_shift: 20.0,
+        palette_order: None,
+        palette_out: None,
+        indexed: false,
         adaptive: true,
         max_depth: 10,
         variance_threshold: 1000.0,
+        split_criterion: SplitMode::Variance,
+        split_threshold: None,
+        min_node_size: 1,
+        lattice: false,
+        balance: false,
+        merge_uniform: false,
+        dither: DitherMode::None,
+        scale: None,
+        stream: false,
+        layout: LayoutMode::Rect,
+        par: PixelAspect::SQUARE,
+        offset_x: 0,
+        offset_y: 0,
+        crop: None,
+        tree_json: None,
+        overlay: None,
+        overlay_base: OverlayBase::Result,
     };
 
     let result = args.validate();
     assert!(result.is_err());
 }
+
+#[test]
+fn test_lock_color_parsing() {
+    let args = vec![
+        "pixel-art-rust",
+        "-w",
+        "32",
+        "--height",
+        "32",
+        "-i",
+        "input.jpg",
+        "-o",
+        "output.png",
+        "--lock-color",
+        "#000000",
+        "--lock-color",
+        "FF8800",
+    ];
+
+    let args = Args::try_parse_from(args).unwrap();
+    assert_eq!(
+        args.lock_colors,
+        vec![Rgba([0, 0, 0, 255]), Rgba([255, 136, 0, 255])]
+    );
+    assert!(args.uses_palette());
+    assert!(args.validate().is_ok());
+
+    let invalid = vec![
+        "pixel-art-rust",
+        "-w",
+        "32",
+        "--height",
+        "32",
+        "-i",
+        "input.jpg",
+        "-o",
+        "output.png",
+        "--lock-color",
+        "#12345",
+    ];
+    assert!(Args::try_parse_from(invalid).is_err());
+}
+
+#[test]
+fn test_shared_palette_is_opt_in() {
+    let parse = |extra: &[&str]| {
+        let base = [
+            "pixel-art-rust",
+            "-w",
+            "32",
+            "--height",
+            "32",
+            "-i",
+            "input.jpg",
+            "-o",
+            "output.png",
+        ];
+        Args::try_parse_from(base.iter().chain(extra)).unwrap()
+    };
+
+    // `--colors` keeps its old meaning: the color count of the cell extractor
+    assert!(!parse(&["--algorithm", "kmeans", "-c", "8"]).uses_palette());
+    assert!(!parse(&[]).uses_palette());
+
+    let args = parse(&["--quantize", "-c", "8"]);
+    assert!(args.quantize);
+    assert!(args.uses_palette());
+    assert!(args.validate().is_ok());
+}
+
+#[test]
+fn test_validation_lock_colors_exceed_palette() {
+    let args = Args {
+        width: Some(32),
+        height: Some(32),
+        cell_size: None,
+        target_width: None,
+        default_size: false,
+        detect_grid: false,
+        input: PathBuf::from("test.jpg"),
+        output: PathBuf::from("out.png"),
+        algorithm: ColorAlgorithm::MedianCut,
+        colors: Some(1),
+        quantize: false,
+        lock_colors: vec![Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255])],
+        ramps: None,
+        hue_shift: 20.0,
+        palette_order: None,
+        palette_out: None,
+        indexed: false,
+        adaptive: false,
+        max_depth: 10,
+        variance_threshold: 50.0,
+        split_criterion: SplitMode::Variance,
+        split_threshold: None,
+        min_node_size: 1,
+        lattice: false,
+        balance: false,
+        merge_uniform: false,
+        dither: DitherMode::None,
+        scale: None,
+        stream: false,
+        layout: LayoutMode::Rect,
+        par: PixelAspect::SQUARE,
+        offset_x: 0,
+        offset_y: 0,
+        crop: None,
+        tree_json: None,
+        overlay: None,
+        overlay_base: OverlayBase::Result,
+    };
+
+    assert!(args.validate().is_err());
+}
+
+#[test]
+fn test_ramps_validation() {
+    let parse = |extra: &[&str]| {
+        let mut argv = vec![
+            "pixel-art-rust",
+            "-w",
+            "32",
+            "--height",
+            "32",
+            "-i",
+            "input.jpg",
+            "-o",
+            "output.png",
+        ];
+        argv.extend_from_slice(extra);
+        Args::try_parse_from(argv).unwrap()
+    };
+
+    let args = parse(&["--ramps", "4", "--hue-shift", "25"]);
+    assert_eq!(args.ramps, Some(4));
+    assert!((args.hue_shift - 25.0).abs() < f32::EPSILON);
+    assert!(args.uses_palette());
+    assert!(args.validate().is_ok());
+
+    assert!(parse(&["--ramps", "1"]).validate().is_err());
+    assert!(parse(&["--ramps", "8", "-c", "4"]).validate().is_err());
+    assert!(
+        parse(&["--ramps", "4", "--hue-shift", "200"])
+            .validate()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_quadtree_debug_outputs_require_adaptive() {
+    let base = [
+        "pixel-art-rust",
+        "-w",
+        "32",
+        "--height",
+        "32",
+        "-i",
+        "test.jpg",
+        "-o",
+        "out.png",
+        "--tree-json",
+        "tree.json",
+        "--overlay",
+        "overlay.png",
+    ];
+
+    let args = Args::try_parse_from(base).unwrap();
+    assert!(args.validate().is_err());
+
+    let args = Args::try_parse_from(base.iter().chain(&["--adaptive"])).unwrap();
+    assert!(args.validate().is_ok());
+    assert_eq!(args.tree_json, Some(std::path::PathBuf::from("tree.json")));
+    assert_eq!(args.overlay, Some(std::path::PathBuf::from("overlay.png")));
+    assert_eq!(args.overlay_base, OverlayBase::Result);
+
+    let args = Args::try_parse_from(
+        base.iter()
+            .chain(&["--adaptive", "--overlay-base", "source"]),
+    )
+    .unwrap();
+    assert_eq!(args.overlay_base, OverlayBase::Source);
+    assert!(args.validate().is_ok());
+
+    let args =
+        Args::try_parse_from(
+            base[..11]
+                .iter()
+                .chain(&["--adaptive", "--overlay-base", "source"]),
+        )
+        .unwrap();
+    assert!(args.validate().is_err());
+}
+
+#[test]
+fn test_stream_validation() {
+    let parse = |output: &str, extra: &[&str]| {
+        let base = [
+            "pixel-art-rust",
+            "-w",
+            "32",
+            "--height",
+            "32",
+            "-i",
+            "test.png",
+            "-o",
+            output,
+            "--stream",
+        ];
+        Args::try_parse_from(base.iter().chain(extra)).unwrap()
+    };
+
+    let args = parse("out.png", &["-c", "8"]);
+    assert!(args.stream);
+    assert!(args.validate().is_ok());
+    assert!(parse("OUT.PNG", &[]).validate().is_ok());
+
+    assert!(parse("out.jpg", &[]).validate().is_err());
+    assert!(parse("out.png", &["--adaptive"]).validate().is_err());
+    assert!(parse("out.png", &["--ramps", "4"]).validate().is_err());
+}
+
+#[test]
+fn test_dither_and_scale_validation() {
+    let parse = |extra: &[&str]| {
+        let base = [
+            "pixel-art-rust",
+            "-w",
+            "32",
+            "--height",
+            "32",
+            "-i",
+            "input.png",
+            "-o",
+            "output.png",
+        ];
+        Args::try_parse_from(base.iter().chain(extra)).unwrap()
+    };
+
+    let args = parse(&[
+        "--quantize",
+        "-c",
+        "8",
+        "--dither",
+        "floyd-steinberg",
+        "--scale",
+        "4",
+    ]);
+    assert_eq!(args.dither, DitherMode::FloydSteinberg);
+    assert_eq!(args.scale, Some(4));
+    assert!(args.validate().is_ok());
+    assert_eq!(parse(&[]).dither, DitherMode::None);
+
+    assert!(parse(&["--dither", "ordered"]).validate().is_err());
+    assert!(
+        parse(&["-c", "8", "--dither", "ordered"])
+            .validate()
+            .is_err()
+    );
+    let error = parse(&["--dither", "ordered"]).validate().unwrap_err();
+    assert!(error.to_string().contains("--ramps"));
+    assert!(
+        parse(&["--ramps", "4", "--dither", "ordered"])
+            .validate()
+            .is_ok()
+    );
+    assert!(parse(&["--scale", "0"]).validate().is_err());
+    assert!(parse(&["--adaptive", "--scale", "2"]).validate().is_err());
+    assert!(
+        parse(&["--adaptive", "-c", "8", "--dither", "ordered"])
+            .validate()
+            .is_err()
+    );
+}
+
+#[test]
+fn test_grid_sizing_modes() {
+    use pixel_art_rust::core::grid::GridSpec;
+
+    let parse = |sizing: &[&str]| {
+        let base = ["pixel-art-rust", "-i", "input.png", "-o", "output.png"];
+        Args::try_parse_from(base.iter().chain(sizing))
+    };
+
+    let spec = |sizing: &[&str]| parse(sizing).unwrap().grid_spec();
+    assert_eq!(
+        spec(&["-w", "32", "--height", "24"]),
+        Some(GridSpec::Cells {
+            columns: 32,
+            rows: 24
+        })
+    );
+    assert_eq!(
+        spec(&["--cell-size", "8"]),
+        Some(GridSpec::CellSize {
+            width: 8,
+            height: 8
+        })
+    );
+    assert_eq!(
+        spec(&["--cell-size", "8x12"]),
+        Some(GridSpec::CellSize {
+            width: 8,
+            height: 12
+        })
+    );
+    assert_eq!(
+        spec(&["--target-width", "64"]),
+        Some(GridSpec::TargetWidth { columns: 64 })
+    );
+    assert_eq!(spec(&["--default-size"]), Some(GridSpec::DefaultCells));
+
+    // Sizing modes are mutually exclusive
+    assert!(parse(&["-w", "32", "--height", "32", "--cell-size", "8"]).is_err());
+    assert!(parse(&["--cell-size", "8", "--target-width", "64"]).is_err());
+    assert!(parse(&["--target-width", "64", "--default-size"]).is_err());
+    assert!(parse(&["--cell-size", "0"]).is_err());
+    assert!(parse(&["--cell-size", "8x"]).is_err());
+
+    // Grid mode needs one of them; adaptive mode only with --lattice
+    let args = parse(&[]).unwrap();
+    assert_eq!(args.grid_spec(), None);
+    let error = args.validate().unwrap_err().to_string();
+    assert!(error.contains("-w/--width and --height, --cell-size, --target-width, --default-size"));
+    assert!(parse(&["--adaptive"]).unwrap().validate().is_ok());
+    assert!(
+        parse(&["--adaptive", "--lattice"])
+            .unwrap()
+            .validate()
+            .is_err()
+    );
+    assert!(parse(&["--target-width", "0"]).unwrap().validate().is_err());
+}
+
+#[test]
+fn test_merge_uniform_needs_grid_mode() {
+    let parse = |options: &[&str]| {
+        let base = ["pixel-art-rust", "-i", "in.png", "-o", "out.png"];
+        Args::try_parse_from(base.iter().chain(options)).unwrap()
+    };
+
+    assert!(
+        parse(&["--cell-size", "8", "--merge-uniform"])
+            .validate()
+            .is_ok()
+    );
+    let error = parse(&["--adaptive", "--merge-uniform"])
+        .validate()
+        .unwrap_err();
+    assert!(error.to_string().contains("--merge-uniform"));
+}
+
+#[test]
+fn test_crop_and_offset_parsing() {
+    use pixel_art_rust::core::grid::Rect;
+
+    let parse = |region: &[&str]| {
+        let base = [
+            "pixel-art-rust",
+            "-i",
+            "in.png",
+            "-o",
+            "out.png",
+            "--default-size",
+        ];
+        Args::try_parse_from(base.iter().chain(region))
+    };
+
+    let args = parse(&[
+        "--crop",
+        "320x240+16+8",
+        "--offset-x",
+        "3",
+        "--offset-y",
+        "1",
+    ])
+    .unwrap();
+    assert_eq!(args.crop, Some(Rect::new(16, 8, 320, 240)));
+    assert_eq!((args.offset_x, args.offset_y), (3, 1));
+    assert_eq!(
+        parse(&["--crop", "64x32"]).unwrap().crop,
+        Some(Rect::new(0, 0, 64, 32))
+    );
+
+    let args = parse(&[]).unwrap();
+    assert_eq!(args.crop, None);
+    assert_eq!((args.offset_x, args.offset_y), (0, 0));
+
+    assert!(parse(&["--crop", "0x32+0+0"]).is_err());
+    assert!(parse(&["--crop", "64x32+5"]).is_err());
+    assert!(parse(&["--crop", "64+5+5"]).is_err());
+}
+
+#[test]
+fn test_detect_grid_replaces_sizing_options() {
+    let parse = |options: &[&str]| {
+        let base = ["pixel-art-rust", "-i", "in.png", "-o", "out.png"];
+        Args::try_parse_from(base.iter().chain(options))
+    };
+
+    let args = parse(&["--detect-grid", "--colors", "8"]).unwrap();
+    assert!(args.detect_grid);
+    assert_eq!(args.grid_spec(), None);
+    assert!(args.validate().is_ok());
+
+    assert!(parse(&["--detect-grid", "--cell-size", "4"]).is_err());
+    assert!(parse(&["--detect-grid", "--offset-x", "2"]).is_err());
+    assert!(parse(&["--detect-grid", "--adaptive"]).is_err());
+    assert!(parse(&["--detect-grid", "--stream"]).is_err());
+}
+
+#[test]
+fn test_layout_validation() {
+    let parse = |options: &[&str]| {
+        let base = [
+            "pixel-art-rust",
+            "-i",
+            "in.png",
+            "-o",
+            "out.png",
+            "--cell-size",
+            "12",
+        ];
+        Args::try_parse_from(base.iter().chain(options))
+    };
+
+    assert_eq!(parse(&[]).unwrap().layout, LayoutMode::Rect);
+    for layout in ["hex-pointy", "hex-flat", "diamond", "brick"] {
+        let args = parse(&["--layout", layout]).unwrap();
+        assert!(args.validate().is_ok(), "{layout}");
+    }
+    assert!(parse(&["--layout", "triangle"]).is_err());
+
+    for conflict in [
+        &["--scale", "2"][..],
+        &["--merge-uniform"],
+        &["--stream"],
+        &["--adaptive"],
+    ] {
+        let args = parse(&[&["--layout", "hex-pointy"][..], conflict].concat()).unwrap();
+        assert!(args.validate().is_err(), "{conflict:?}");
+    }
+}
+
+#[test]
+fn test_pixel_aspect_parsing() {
+    let parse = |options: &[&str]| {
+        let base = [
+            "pixel-art-rust",
+            "-i",
+            "in.png",
+            "-o",
+            "out.png",
+            "--target-width",
+            "80",
+        ];
+        Args::try_parse_from(base.iter().chain(options))
+    };
+
+    assert_eq!(parse(&[]).unwrap().par, PixelAspect::SQUARE);
+    let args = parse(&["--par", "2:1", "--scale", "4"]).unwrap();
+    assert_eq!(args.par, PixelAspect::new(2, 1));
+    assert!(args.validate().is_ok());
+    assert_eq!(
+        parse(&["--par", "8:7"]).unwrap().par,
+        PixelAspect::new(8, 7)
+    );
+
+    for invalid in ["2", "0:1", "2:0", "2x1", "a:b"] {
+        assert!(parse(&["--par", invalid]).is_err(), "{invalid}");
+    }
+
+    let args = Args::try_parse_from([
+        "pixel-art-rust",
+        "-i",
+        "in.png",
+        "-o",
+        "out.png",
+        "--adaptive",
+        "--par",
+        "2:1",
+    ])
+    .unwrap();
+    assert!(args.validate().is_err());
+}
+
+#[test]
+fn test_palette_order_and_export_options() {
+    let parse = |options: &[&str]| {
+        let base = [
+            "pixel-art-rust",
+            "-i",
+            "in.png",
+            "-o",
+            "out.png",
+            "--cell-size",
+            "8",
+        ];
+        Args::try_parse_from(base.iter().chain(options)).unwrap()
+    };
+
+    let args = parse(&[
+        "--quantize",
+        "--palette-order",
+        "ramps",
+        "--palette-out",
+        "colors.gpl",
+        "--indexed",
+    ]);
+    assert_eq!(args.palette_order, Some(PaletteOrderMode::Ramps));
+    assert_eq!(args.palette_out, Some(PathBuf::from("colors.gpl")));
+    assert!(args.indexed);
+    assert!(args.validate().is_ok());
+
+    // There is no palette to order or export without quantizing
+    assert!(parse(&["--palette-order", "hue"]).validate().is_err());
+    assert!(parse(&["--palette-out", "colors.hex"]).validate().is_err());
+    assert!(parse(&["--indexed"]).validate().is_err());
+    assert!(
+        parse(&["--quantize", "--indexed", "--stream"])
+            .validate()
+            .is_err()
+    );
+}
@@ -144,3 +144,104 @@ fn test_hierarchical_clustering() {
         }
     }
 }
+
+#[test]
+fn test_hierarchical_clustering_order_independent() {
+    let mut pixels = vec![
+        Rgba([255, 0, 0, 255]),
+        Rgba([0, 255, 0, 255]),
+        Rgba([250, 10, 10, 255]),
+        Rgba([0, 0, 255, 255]),
+        Rgba([10, 245, 10, 255]),
+        Rgba([5, 5, 245, 255]),
+    ];
+
+    let forward = hierarchical_color_clustering(&pixels, 3);
+    pixels.reverse();
+    let reversed = hierarchical_color_clustering(&pixels, 3);
+
+    assert_eq!(forward, reversed);
+    assert_eq!(forward.len(), 3);
+    assert!(forward.iter().all(|cluster| cluster.len() == 2));
+}
+
+#[test]
+fn test_dendrogram_cut_levels() {
+    let pixels = vec![
+        Rgba([0, 0, 0, 255]),
+        Rgba([0, 0, 0, 255]),
+        Rgba([10, 10, 10, 255]),
+        Rgba([255, 255, 255, 255]),
+        Rgba([245, 245, 245, 255]),
+        Rgba([255, 0, 0, 255]),
+    ];
+
+    for linkage in [Linkage::Ward, Linkage::Average] {
+        let dendrogram = Dendrogram::build(&pixels, linkage);
+
+        // Duplicates collapse into a single weighted leaf
+        assert_eq!(dendrogram.leaf_count(), 5);
+        assert_eq!(dendrogram.merges().len(), 4);
+        assert_eq!(dendrogram.merges().last().unwrap().weight, 6);
+        assert!(
+            dendrogram
+                .merges()
+                .windows(2)
+                .all(|pair| pair[0].distance <= pair[1].distance)
+        );
+
+        for k in 1..=5 {
+            let clusters = dendrogram.cut(k);
+            assert_eq!(clusters.len(), k);
+            assert_eq!(clusters.iter().map(Vec::len).sum::<usize>(), 5);
+        }
+        assert_eq!(dendrogram.cut(10).len(), 5);
+
+        let colors = dendrogram.cluster_colors(3);
+        assert_eq!(colors.len(), 3);
+        assert!(colors.iter().any(|c| c.0[0] > 200 && c.0[1] < 50));
+    }
+}
+
+#[test]
+fn test_ward_linkage_weighs_pixel_counts() {
+    let black = Rgba([0, 0, 0, 255]);
+    let dark = Rgba([20, 20, 20, 255]);
+    let gray = Rgba([60, 60, 60, 255]);
+
+    // One pixel each: the two closest colors merge first
+    let dendrogram = Dendrogram::build(&[black, dark, gray], Linkage::Ward);
+    let first = dendrogram.merges()[0];
+    assert_eq!(
+        (first.left.min(first.right), first.left.max(first.right)),
+        (0, 1)
+    );
+
+    // Merging two large clusters costs more than absorbing a single far pixel
+    let mut pixels = vec![black; 500];
+    pixels.extend(std::iter::repeat_n(dark, 500));
+    pixels.push(gray);
+    let dendrogram = Dendrogram::build(&pixels, Linkage::Ward);
+    let first = dendrogram.merges()[0];
+    assert_eq!(
+        (first.left.min(first.right), first.left.max(first.right)),
+        (1, 2)
+    );
+    assert_eq!(first.weight, 501);
+}
+
+#[test]
+fn test_parse_hex_color_errors() {
+    use pixel_art_rust::core::error::PixelArtError;
+
+    assert_eq!(
+        parse_hex_color("#ff8800").unwrap(),
+        Rgba([255, 136, 0, 255])
+    );
+    for invalid in ["#12345", "+12345", "#GG0000", "#ff88000"] {
+        match parse_hex_color(invalid) {
+            Err(PixelArtError::InvalidColor(value)) => assert_eq!(value, invalid),
+            other => panic!("expected InvalidColor for {invalid}, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use pixel_art_rust::core::color::KMeansExtractor;
+use pixel_art_rust::core::detect::*;
+use pixel_art_rust::core::error::PixelArtError;
+use pixel_art_rust::core::pixel_art::PixelArtConverter;
+use std::io::Cursor;
+
+// Blocky sprite with a small fixed palette, like real pixel art
+fn sprite(width: u32, height: u32) -> RgbaImage {
+    let palette = [
+        Rgba([20, 20, 40, 255]),
+        Rgba([200, 60, 60, 255]),
+        Rgba([60, 180, 90, 255]),
+        Rgba([240, 220, 120, 255]),
+        Rgba([70, 110, 220, 255]),
+    ];
+    let mut state = 0x2545_f491_u32;
+    RgbaImage::from_fn(width, height, |_, _| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        palette[(state % palette.len() as u32) as usize]
+    })
+}
+
+// Nearest-neighbour upscale by `factor`, shifted right and down by `offset` pixels
+fn upscale(sprite: &RgbaImage, factor: u32, offset: (u32, u32)) -> RgbaImage {
+    let (width, height) = sprite.dimensions();
+    RgbaImage::from_fn(
+        width * factor + offset.0 + 3,
+        height * factor + offset.1 + 2,
+        |x, y| {
+            let col = (x.saturating_sub(offset.0) / factor).min(width - 1);
+            let row = (y.saturating_sub(offset.1) / factor).min(height - 1);
+            *sprite.get_pixel(col, row)
+        },
+    )
+}
+
+#[test]
+fn test_detects_integer_upscale_and_phase() {
+    let native = sprite(24, 18);
+    let detected = GridDetector::new()
+        .detect(&upscale(&native, 4, (2, 3)))
+        .unwrap();
+
+    assert!((detected.cell_width - 4.0).abs() < 0.01, "{detected:?}");
+    assert!((detected.cell_height - 4.0).abs() < 0.01, "{detected:?}");
+    assert_eq!(detected.offset(), (2, 3));
+    assert_eq!((detected.columns, detected.rows), (24, 18));
+    assert!(detected.is_integral());
+    assert!(detected.confidence > 0.8, "{detected:?}");
+
+    let grid = detected.grid();
+    assert_eq!((grid.width(), grid.height()), (24, 18));
+    assert_eq!((grid.cell_width(), grid.cell_height()), (4, 4));
+}
+
+#[test]
+fn test_recovers_sprite_from_integer_upscale() {
+    let native = sprite(20, 16);
+    let image = DynamicImage::ImageRgba8(upscale(&native, 5, (1, 4)));
+    let detected = GridDetector::new().detect(&image.to_rgba8()).unwrap();
+
+    let (offset_x, offset_y) = detected.offset();
+    let recovered = PixelArtConverter::builder()
+        .grid(detected.spec())
+        .offset(offset_x, offset_y)
+        .scale(1)
+        .build()
+        .unwrap()
+        .convert(&image)
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(recovered, native);
+}
+
+#[test]
+fn test_detects_non_integer_upscale() {
+    let native = sprite(40, 30);
+    let image =
+        DynamicImage::ImageRgba8(native.clone()).resize_exact(140, 105, FilterType::Nearest);
+    let detected = GridDetector::new().detect(&image.to_rgba8()).unwrap();
+    assert!((detected.cell_width - 3.5).abs() < 0.01, "{detected:?}");
+    assert!((detected.cell_height - 3.5).abs() < 0.01, "{detected:?}");
+    assert!(!detected.is_integral());
+
+    // Resampling makes the cells whole pixels so the grid lines up again
+    let (resampled, aligned) = detected.resample(&image);
+    assert!(aligned.is_integral());
+    let (offset_x, offset_y) = aligned.offset();
+    let recovered = PixelArtConverter::builder()
+        .grid(aligned.spec())
+        .offset(offset_x, offset_y)
+        .scale(1)
+        .extractor(KMeansExtractor {
+            k: 1,
+            max_iterations: 1,
+        })
+        .build()
+        .unwrap()
+        .convert(&resampled)
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(recovered.dimensions(), (40, 30));
+
+    let exact = recovered
+        .pixels()
+        .zip(native.pixels())
+        .filter(|(a, b)| a == b)
+        .count();
+    assert!(
+        exact >= 40 * 30 * 95 / 100,
+        "{exact} of 1200 cells recovered"
+    );
+}
+
+#[test]
+fn test_detects_grid_through_jpeg_artifacts() {
+    let native = sprite(32, 24);
+    let image = DynamicImage::ImageRgba8(native).resize_exact(96 + 32, 96, FilterType::Nearest);
+
+    let mut bytes = Vec::new();
+    image
+        .to_rgb8()
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Jpeg)
+        .unwrap();
+    let jpeg = image::load_from_memory(&bytes).unwrap().to_rgba8();
+
+    let detected = GridDetector::new().detect(&jpeg).unwrap();
+    assert!((detected.cell_width - 4.0).abs() < 0.05, "{detected:?}");
+    assert!((detected.cell_height - 4.0).abs() < 0.05, "{detected:?}");
+}
+
+#[test]
+fn test_rejects_images_without_a_grid() {
+    // Smooth gradient with noise: edges everywhere, none of them periodic
+    let mut state = 12345u32;
+    let noise = RgbaImage::from_fn(128, 96, |x, y| {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        let jitter = (state >> 24) as u8 / 4;
+        Rgba([(x * 2) as u8 / 2 + jitter, (y * 2) as u8, jitter, 255])
+    });
+    assert!(matches!(
+        GridDetector::new().detect(&noise),
+        Err(PixelArtError::GridNotDetected(_))
+    ));
+
+    assert!(matches!(
+        GridDetector::new().detect(&RgbaImage::from_pixel(64, 64, Rgba([9, 9, 9, 255]))),
+        Err(PixelArtError::GridNotDetected(_))
+    ));
+}
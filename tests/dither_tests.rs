@@ -0,0 +1,64 @@
+use image::Rgba;
+use pixel_art_rust::core::dither::*;
+use pixel_art_rust::core::quantization::map_to_palette;
+
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+fn count_white(colors: &[Rgba<u8>]) -> usize {
+    colors.iter().filter(|&&color| color == WHITE).count()
+}
+
+#[test]
+fn test_no_dithering_maps_to_nearest() {
+    let palette = [BLACK, WHITE, Rgba([255, 0, 0, 255])];
+    let original: Vec<Rgba<u8>> = (0..12)
+        .map(|i| Rgba([i * 20, 255 - i * 20, i * 5, 255]))
+        .collect();
+
+    let mut colors = original.clone();
+    dither_to_palette(&mut colors, 4, &palette, Dithering::None);
+    for (mapped, color) in colors.iter().zip(&original) {
+        assert_eq!(*mapped, map_to_palette(color, &palette));
+    }
+}
+
+#[test]
+fn test_floyd_steinberg_keeps_average_tone() {
+    // Mid gray has no close palette entry, so it must become a mix of black and white
+    let mut colors = vec![Rgba([128, 128, 128, 255]); 16 * 16];
+    dither_to_palette(&mut colors, 16, &[BLACK, WHITE], Dithering::FloydSteinberg);
+
+    assert!(colors.iter().all(|&color| color == BLACK || color == WHITE));
+    let white = count_white(&colors);
+    assert!(
+        (96..=160).contains(&white),
+        "{white} of 256 cells are white"
+    );
+}
+
+#[test]
+fn test_ordered_dithering_tiles_bayer_pattern() {
+    let mut colors = vec![Rgba([128, 128, 128, 255]); 8 * 8];
+    dither_to_palette(&mut colors, 8, &[BLACK, WHITE], Dithering::Ordered);
+
+    // Every 4x4 tile gets the same pattern, roughly half of it white
+    let tile = |row: usize, col: usize| -> Vec<Rgba<u8>> {
+        (0..4)
+            .flat_map(|r| (0..4).map(move |c| (r, c)))
+            .map(|(r, c)| colors[(row + r) * 8 + col + c])
+            .collect()
+    };
+    assert!((6..=10).contains(&count_white(&tile(0, 0))));
+    assert_eq!(tile(0, 0), tile(4, 4));
+    assert_eq!(tile(0, 4), tile(4, 0));
+}
+
+#[test]
+fn test_exact_palette_colors_are_kept() {
+    for dithering in [Dithering::FloydSteinberg, Dithering::Ordered] {
+        let mut colors = vec![BLACK, WHITE, WHITE, BLACK];
+        dither_to_palette(&mut colors, 2, &[BLACK, WHITE], dithering);
+        assert_eq!(colors, vec![BLACK, WHITE, WHITE, BLACK]);
+    }
+}
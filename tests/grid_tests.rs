@@ -1,4 +1,4 @@
-use pixel_art_rust::core::grid::Grid;
+use pixel_art_rust::core::grid::{Grid, GridSpec};
 
 #[test]
 fn test_grid_creation_with_valid_dimensions() {
@@ -90,3 +90,151 @@ fn test_grid_boundaries_edge_cases() {
     assert_eq!(w, 0);
     assert_eq!(h, 0);
 }
+
+#[test]
+fn test_grid_spec_resolves_per_image() {
+    let cells = GridSpec::Cells {
+        columns: 4,
+        rows: 2,
+    };
+    let grid = cells.resolve(100, 50).unwrap();
+    assert_eq!((grid.width(), grid.height()), (4, 2));
+    assert_eq!((grid.cell_width(), grid.cell_height()), (25, 25));
+    let grid = cells.resolve(40, 80).unwrap();
+    assert_eq!((grid.cell_width(), grid.cell_height()), (10, 40));
+
+    let sized = GridSpec::CellSize {
+        width: 8,
+        height: 12,
+    }
+    .resolve(100, 50)
+    .unwrap();
+    assert_eq!((sized.width(), sized.height()), (12, 4));
+    assert_eq!((sized.cell_width(), sized.cell_height()), (8, 12));
+
+    // 64 across a 4:3 image gives 48 rows
+    let target = GridSpec::TargetWidth { columns: 64 }
+        .resolve(1024, 768)
+        .unwrap();
+    assert_eq!((target.width(), target.height()), (64, 48));
+    assert_eq!((target.cell_width(), target.cell_height()), (16, 16));
+
+    assert_eq!(
+        GridSpec::from(Grid::new(800, 600, 32, 24)),
+        GridSpec::Cells {
+            columns: 32,
+            rows: 24
+        }
+    );
+}
+
+#[test]
+fn test_grid_spec_errors() {
+    use pixel_art_rust::core::error::PixelArtError;
+
+    assert!(matches!(
+        GridSpec::Cells {
+            columns: 0,
+            rows: 3
+        }
+        .validate(),
+        Err(PixelArtError::InvalidGrid { .. })
+    ));
+    assert!(matches!(
+        GridSpec::TargetWidth { columns: 0 }.validate(),
+        Err(PixelArtError::InvalidGrid { .. })
+    ));
+    assert!(matches!(
+        GridSpec::Cells {
+            columns: 16,
+            rows: 16
+        }
+        .resolve(8, 32),
+        Err(PixelArtError::GridLargerThanImage { .. })
+    ));
+    assert!(matches!(
+        GridSpec::CellSize {
+            width: 16,
+            height: 4
+        }
+        .resolve(8, 32),
+        Err(PixelArtError::CellLargerThanImage { .. })
+    ));
+}
+
+#[test]
+fn test_default_grid_spec() {
+    // Square cells with about 64 along the longer side, independent of content
+    let grid = GridSpec::DefaultCells.resolve(1280, 720).unwrap();
+    assert_eq!((grid.cell_width(), grid.cell_height()), (20, 20));
+    assert_eq!((grid.width(), grid.height()), (64, 36));
+
+    let portrait = GridSpec::DefaultCells.resolve(300, 1000).unwrap();
+    assert_eq!(portrait.cell_width(), 15);
+    assert_eq!((portrait.width(), portrait.height()), (20, 66));
+
+    // Small images fall back to one pixel per cell
+    let tiny = GridSpec::DefaultCells.resolve(40, 30).unwrap();
+    assert_eq!((tiny.width(), tiny.height()), (40, 30));
+}
+
+#[test]
+fn test_grid_region_from_crop_and_offset() {
+    use pixel_art_rust::core::error::PixelArtError;
+    use pixel_art_rust::core::grid::{Rect, grid_region};
+
+    // Without a crop the frame is the whole image
+    let (frame, area) = grid_region(100, 80, None, (3, 2)).unwrap();
+    assert_eq!(frame, Rect::new(0, 0, 100, 80));
+    assert_eq!(area, Rect::new(3, 2, 97, 78));
+
+    // The offset is measured from the crop origin
+    let crop = Rect::new(10, 20, 50, 40);
+    let (frame, area) = grid_region(100, 80, Some(crop), (4, 0)).unwrap();
+    assert_eq!(frame, crop);
+    assert_eq!(area, Rect::new(14, 20, 46, 40));
+
+    assert!(matches!(
+        grid_region(100, 80, Some(Rect::new(60, 0, 50, 40)), (0, 0)),
+        Err(PixelArtError::RegionOutsideImage { .. })
+    ));
+    assert!(matches!(
+        grid_region(100, 80, Some(crop), (50, 0)),
+        Err(PixelArtError::RegionOutsideImage { .. })
+    ));
+}
+
+#[test]
+fn test_grid_spec_with_pixel_aspect() {
+    use pixel_art_rust::core::grid::PixelAspect;
+
+    // C64 multicolor: 160 columns of 2:1 pixels over a 320x200 picture
+    let wide = PixelAspect::new(2, 1);
+    let grid = GridSpec::TargetWidth { columns: 160 }
+        .resolve_with_aspect(320, 200, wide)
+        .unwrap();
+    assert_eq!((grid.width(), grid.height()), (160, 200));
+    assert_eq!((grid.cell_width(), grid.cell_height()), (2, 1));
+
+    let grid = GridSpec::DefaultCells
+        .resolve_with_aspect(1280, 720, wide)
+        .unwrap();
+    assert_eq!((grid.cell_width(), grid.cell_height()), (22, 11));
+
+    // Cells are clamped to a thin image instead of leaving no columns
+    let thin = GridSpec::DefaultCells
+        .resolve_with_aspect(3, 200, wide)
+        .unwrap();
+    assert_eq!((thin.cell_width(), thin.cell_height()), (3, 3));
+    assert_eq!((thin.width(), thin.height()), (1, 66));
+
+    // Explicit cells are kept as given
+    let cells = GridSpec::Cells {
+        columns: 10,
+        rows: 10,
+    };
+    let grid = cells.resolve_with_aspect(100, 100, wide).unwrap();
+    assert_eq!((grid.cell_width(), grid.cell_height()), (10, 10));
+    assert_eq!(PixelAspect::new(8, 7).scaled_width(7), 8);
+    assert_eq!(PixelAspect::new(1, 4).scaled_width(1), 1);
+}
@@ -0,0 +1,108 @@
+use image::{Rgba, RgbaImage};
+use pixel_art_rust::core::color::{AverageColorExtractor, ColorExtractor, rgba_to_lab_values};
+use pixel_art_rust::core::integral::*;
+
+fn gradient_image(width: u32, height: u32) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        *pixel = Rgba([
+            (x * 29 % 256) as u8,
+            (y * 41 % 256) as u8,
+            ((x + y) * 13 % 256) as u8,
+            255,
+        ]);
+    }
+    image
+}
+
+fn region_pixels(image: &RgbaImage, x: u32, y: u32, w: u32, h: u32) -> Vec<Rgba<u8>> {
+    let mut pixels = Vec::new();
+    for py in y..y + h {
+        for px in x..x + w {
+            pixels.push(*image.get_pixel(px, py));
+        }
+    }
+    pixels
+}
+
+#[test]
+fn test_rgb_region_mean_matches_average_extractor() {
+    let image = gradient_image(13, 9);
+    let integral = IntegralImage::new(&image, IntegralSpace::Rgb);
+
+    for (x, y, w, h) in [(0, 0, 13, 9), (3, 2, 5, 4), (12, 8, 1, 1), (1, 0, 7, 9)] {
+        let pixels = region_pixels(&image, x, y, w, h);
+        let (mean, _variance) = integral.region_stats(x, y, w, h);
+        assert_eq!(mean, AverageColorExtractor.extract_color(&pixels));
+    }
+}
+
+#[test]
+fn test_lab_region_variance_matches_brute_force() {
+    let image = gradient_image(16, 11);
+    let integral = IntegralImage::new(&image, IntegralSpace::Lab);
+
+    for (x, y, w, h) in [(0, 0, 16, 11), (4, 3, 6, 5), (15, 10, 1, 1)] {
+        let labs: Vec<[f32; 3]> = region_pixels(&image, x, y, w, h)
+            .iter()
+            .map(rgba_to_lab_values)
+            .collect();
+        let n = labs.len() as f64;
+        let mean: Vec<f64> = (0..3)
+            .map(|c| labs.iter().map(|lab| lab[c] as f64).sum::<f64>() / n)
+            .collect();
+        let expected: f64 = labs
+            .iter()
+            .map(|lab| {
+                (0..3)
+                    .map(|c| (lab[c] as f64 - mean[c]).powi(2))
+                    .sum::<f64>()
+            })
+            .sum::<f64>()
+            / n;
+
+        let (_mean, variance) = integral.region_stats(x, y, w, h);
+        assert!((variance - expected).abs() < 1e-3 * expected.max(1.0));
+    }
+}
+
+#[test]
+fn test_uniform_region_stats() {
+    let mut image = RgbaImage::new(6, 6);
+    for pixel in image.pixels_mut() {
+        *pixel = Rgba([128, 64, 200, 255]);
+    }
+
+    let integral = IntegralImage::new(&image, IntegralSpace::Lab);
+    let (mean, variance) = integral.region_stats(1, 1, 4, 5);
+
+    assert_eq!(mean, Rgba([128, 64, 200, 255]));
+    assert!(variance < 1e-6);
+    assert_eq!(integral.region_sum(0, 0, 0, 0), [0.0; 3]);
+}
+
+#[test]
+fn test_rgb_sums_are_exact_beyond_32_bits() {
+    // 300x300 white squares sum to 90000 * 255^2, past what a 32-bit table entry holds
+    let image = RgbaImage::from_pixel(301, 300, Rgba([255, 255, 255, 255]));
+    let integral = IntegralImage::new(&image, IntegralSpace::Rgb);
+
+    let count = 300.0 * 300.0;
+    assert_eq!(integral.region_sum(1, 0, 300, 300), [count * 255.0; 3]);
+    assert_eq!(
+        integral.region_square_sum(0, 0, 300, 300),
+        [count * 255.0 * 255.0; 3]
+    );
+    assert_eq!(integral.region_stats(1, 0, 300, 300).1, 0.0);
+    assert_eq!(integral.region_sum(5, 5, 0, 7), [0.0; 3]);
+}
+
+#[test]
+fn test_table_memory_for_8k_input() {
+    // 7680x4320 is 33M pixels: RGB tables stay under 1 GB, Lab tables are twice that
+    let rgb = IntegralImage::table_bytes(7680, 4320, IntegralSpace::Rgb);
+    let lab = IntegralImage::table_bytes(7680, 4320, IntegralSpace::Lab);
+    assert_eq!(rgb, 7681 * 4321 * 24);
+    assert!(rgb < 1 << 30);
+    assert_eq!(lab, 2 * rgb);
+}
@@ -0,0 +1,145 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use pixel_art_rust::core::error::PixelArtError;
+use pixel_art_rust::core::grid::Grid;
+use pixel_art_rust::core::layout::*;
+use pixel_art_rust::core::pixel_art::PixelArtConverter;
+use std::collections::HashMap;
+
+const LAYOUTS: [GridLayout; 5] = [
+    GridLayout::Rectangular,
+    GridLayout::Hexagonal(HexOrientation::Pointy),
+    GridLayout::Hexagonal(HexOrientation::Flat),
+    GridLayout::Diamond,
+    GridLayout::Brick,
+];
+
+#[test]
+fn test_every_pixel_belongs_to_a_cell() {
+    for layout in LAYOUTS {
+        let tiles = TiledGrid::new(layout, Grid::new(100, 90, 8, 6));
+        assert_eq!(tiles.extent(), (96, 90));
+
+        let mut sizes: HashMap<(u32, u32), u32> = HashMap::new();
+        for y in 0..90 {
+            for x in 0..96 {
+                let cell = tiles.cell_at(x, y).unwrap();
+                assert!(tiles.is_cell(cell.0, cell.1), "{layout:?} {cell:?}");
+                *sizes.entry(cell).or_default() += 1;
+            }
+        }
+        assert_eq!(tiles.cell_at(96, 0), None);
+        assert_eq!(tiles.cell_at(0, 90), None);
+
+        // Every cell is reached, and whole cells are about a 12x15 cell's area; diamonds
+        // fill half of their bounding box
+        assert_eq!(sizes.len(), tiles.cell_count(), "{layout:?}");
+        let area = if layout == GridLayout::Diamond {
+            90
+        } else {
+            180
+        };
+        let largest = *sizes.values().max().unwrap();
+        assert!(largest.abs_diff(area) <= 10, "{layout:?} {largest}");
+    }
+}
+
+#[test]
+fn test_rectangular_layout_matches_grid() {
+    let grid = Grid::new(64, 48, 8, 4);
+    let tiles = TiledGrid::new(GridLayout::Rectangular, grid.clone());
+    assert_eq!(tiles.cell_count(), grid.cell_count() as usize);
+    assert!(tiles.iter_cells().eq(grid.iter_cells()));
+
+    for (row, col) in grid.iter_cells() {
+        let (x, y, w, h) = grid.get_cell_bounds(row, col);
+        assert_eq!(tiles.cell_at(x, y), Some((row, col)));
+        assert_eq!(tiles.cell_at(x + w - 1, y + h - 1), Some((row, col)));
+    }
+}
+
+#[test]
+fn test_staggered_cells_and_shapes() {
+    // 20x16 cells: odd rows of pointy hexes and bricks start with a half cell
+    let grid = Grid::new(80, 64, 4, 4);
+    let hex = TiledGrid::new(GridLayout::Hexagonal(HexOrientation::Pointy), grid.clone());
+    assert!(hex.is_cell(1, 4) && !hex.is_cell(0, 4));
+    assert_eq!(hex.cell_center(0, 0), (10.0, 8.0));
+    assert_eq!(hex.cell_center(1, 0), (0.0, 24.0));
+
+    // Vertices reach across the row boundary: the top one of (1, 1) at x = 20 and the
+    // bottom one of (0, 1) at x = 30
+    assert_eq!(hex.cell_at(20, 15), Some((1, 1)));
+    assert_eq!(hex.cell_at(29, 16), Some((0, 1)));
+    assert_eq!(hex.cell_at(21, 16), Some((1, 1)));
+
+    let brick = TiledGrid::new(GridLayout::Brick, grid.clone());
+    assert_eq!(brick.cell_at(9, 16), Some((1, 0)));
+    assert_eq!(brick.cell_at(10, 16), Some((1, 1)));
+    assert_eq!(brick.cell_at(10, 15), Some((0, 0)));
+
+    let flat = TiledGrid::new(GridLayout::Hexagonal(HexOrientation::Flat), grid.clone());
+    assert!(flat.is_cell(4, 1) && !flat.is_cell(4, 0));
+    assert_eq!(flat.cell_center(0, 1), (30.0, 0.0));
+
+    // Diamonds: the center belongs to the tile, the bounding box corners to neighbours
+    let diamond = TiledGrid::new(GridLayout::Diamond, grid);
+    assert_eq!(diamond.index_rows(), 9);
+    assert_eq!(diamond.cell_center(1, 0), (10.0, 8.0));
+    assert_eq!(diamond.cell_at(10, 8), Some((1, 0)));
+    assert_eq!(diamond.cell_at(1, 1), Some((0, 0)));
+    assert_eq!(diamond.cell_at(19, 15), Some((2, 1)));
+}
+
+#[test]
+fn test_converter_paints_each_cell_in_one_color() {
+    let image = RgbaImage::from_fn(90, 72, |x, y| Rgba([(x * 2) as u8, (y * 3) as u8, 90, 255]));
+    let image = DynamicImage::ImageRgba8(image);
+
+    for layout in LAYOUTS {
+        let converter = PixelArtConverter::builder()
+            .grid_cells(6, 6)
+            .layout(layout)
+            .build()
+            .unwrap();
+        let sequential = converter.convert(&image).unwrap().to_rgba8();
+        let parallel = converter.convert_parallel(&image).unwrap().to_rgba8();
+        assert_eq!(sequential, parallel, "{layout:?}");
+        assert_eq!(sequential.dimensions(), (90, 72));
+
+        let tiles = TiledGrid::new(layout, Grid::new(90, 72, 6, 6));
+        let mut cell_colors = HashMap::new();
+        for (x, y, pixel) in sequential.enumerate_pixels() {
+            let cell = tiles.cell_at(x, y).unwrap();
+            assert_eq!(
+                cell_colors.entry(cell).or_insert(*pixel),
+                pixel,
+                "{layout:?}"
+            );
+        }
+        assert_eq!(cell_colors.len(), tiles.cell_count());
+    }
+}
+
+#[test]
+fn test_layouts_reject_unsupported_options() {
+    let hex = GridLayout::Hexagonal(HexOrientation::Pointy);
+    let rejected = [
+        PixelArtConverter::builder()
+            .grid_cells(4, 4)
+            .layout(hex)
+            .scale(2)
+            .build(),
+        PixelArtConverter::builder()
+            .grid_cells(4, 4)
+            .layout(hex)
+            .merge_uniform(true)
+            .build(),
+        PixelArtConverter::builder()
+            .quadtree(pixel_art_rust::core::quadtree::QuadTreeConfig::new(4, 10.0))
+            .layout(GridLayout::Diamond)
+            .build(),
+    ];
+    for result in rejected {
+        assert!(matches!(result, Err(PixelArtError::InvalidConfig(_))));
+    }
+}
@@ -0,0 +1,250 @@
+use image::Rgba;
+use pixel_art_rust::core::palette::*;
+use pixel_art_rust::core::quantization::{PaletteQuantizer, QuantizationMethod};
+
+fn sample_palette() -> Vec<Rgba<u8>> {
+    vec![
+        Rgba([200, 40, 40, 255]),   // light red
+        Rgba([40, 40, 200, 255]),   // blue
+        Rgba([255, 255, 255, 255]), // white
+        Rgba([100, 10, 10, 255]),   // dark red
+        Rgba([0, 0, 0, 255]),       // black
+        Rgba([20, 20, 100, 255]),   // dark blue
+    ]
+}
+
+#[test]
+fn test_sort_by_luminance() {
+    let mut palette = sample_palette();
+    sort_palette(&mut palette, PaletteOrder::Luminance);
+
+    assert_eq!(palette[0], Rgba([0, 0, 0, 255]));
+    assert_eq!(palette[palette.len() - 1], Rgba([255, 255, 255, 255]));
+    for pair in palette.windows(2) {
+        assert!(rgba_to_lch(&pair[0]).l <= rgba_to_lch(&pair[1]).l);
+    }
+}
+
+#[test]
+fn test_sort_by_hue_puts_neutrals_first() {
+    let mut palette = sample_palette();
+    sort_palette(&mut palette, PaletteOrder::Hue);
+
+    assert_eq!(palette[0], Rgba([0, 0, 0, 255]));
+    assert_eq!(palette[1], Rgba([255, 255, 255, 255]));
+    assert_eq!(palette.len(), 6);
+}
+
+#[test]
+fn test_hue_sort_is_total() {
+    let mut palette = sample_palette();
+    palette.extend([Rgba([0, 0, 0, 255]), Rgba([0, 0, 0, 255])]);
+    let mut reversed: Vec<Rgba<u8>> = palette.iter().rev().copied().collect();
+    sort_palette(&mut palette, PaletteOrder::Hue);
+    sort_palette(&mut reversed, PaletteOrder::Hue);
+    assert_eq!(palette, reversed);
+}
+
+#[test]
+fn test_similarity_chain_starts_dark_and_keeps_all_colors() {
+    let mut palette = sample_palette();
+    sort_palette(&mut palette, PaletteOrder::SimilarityChain);
+
+    assert_eq!(palette[0], Rgba([0, 0, 0, 255]));
+    let mut sorted = palette.clone();
+    sorted.sort_by_key(|c| c.0);
+    let mut expected = sample_palette();
+    expected.sort_by_key(|c| c.0);
+    assert_eq!(sorted, expected);
+}
+
+#[test]
+fn test_group_into_ramps() {
+    let ramps = group_into_ramps(&sample_palette(), 30.0);
+
+    assert_eq!(ramps.len(), 3);
+    assert_eq!(
+        ramps[0],
+        vec![Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255])]
+    );
+    for ramp in &ramps[1..] {
+        assert_eq!(ramp.len(), 2);
+        assert!(rgba_to_lch(&ramp[0]).l < rgba_to_lch(&ramp[1]).l);
+    }
+}
+
+#[test]
+fn test_near_duplicate_detection() {
+    let palette = vec![
+        Rgba([100, 100, 100, 255]),
+        Rgba([0, 200, 0, 255]),
+        Rgba([102, 101, 100, 255]),
+    ];
+
+    let pairs = find_near_duplicates(&palette, 3.0);
+    assert_eq!(pairs.len(), 1);
+    assert_eq!((pairs[0].0, pairs[0].1), (0, 2));
+
+    let merged = merge_near_duplicates(&palette, 3.0);
+    assert_eq!(
+        merged,
+        vec![Rgba([100, 100, 100, 255]), Rgba([0, 200, 0, 255])]
+    );
+}
+
+#[test]
+fn test_quantizer_applies_palette_order() {
+    let pixels: Vec<Rgba<u8>> = (0..=255u8)
+        .step_by(5)
+        .map(|v| Rgba([v, 255 - v, v / 2, 255]))
+        .collect();
+
+    let palette = PaletteQuantizer::new(QuantizationMethod::MedianCut, 6)
+        .with_order(PaletteOrder::Luminance)
+        .build_palette(&pixels);
+
+    assert!(!palette.is_empty());
+    for pair in palette.windows(2) {
+        assert!(rgba_to_lch(&pair[0]).l <= rgba_to_lch(&pair[1]).l);
+    }
+}
+
+#[test]
+fn test_palette_export_formats() {
+    let palette = vec![Rgba([255, 136, 0, 255]), Rgba([0, 0, 0, 255])];
+    assert_eq!(palette_to_hex(&palette), "ff8800\n000000\n");
+
+    let gpl = palette_to_gpl(&palette, "sunset");
+    let lines: Vec<&str> = gpl.lines().collect();
+    assert_eq!(lines[..2], ["GIMP Palette", "Name: sunset"]);
+    assert_eq!(lines[4..], ["255 136   0\t#ff8800", "  0   0   0\t#000000"]);
+}
+
+#[test]
+fn test_indexed_png_follows_palette_order() {
+    use image::RgbaImage;
+    use pixel_art_rust::core::error::PixelArtError;
+
+    let palette = vec![
+        Rgba([250, 250, 250, 255]),
+        Rgba([10, 10, 10, 255]),
+        Rgba([200, 0, 0, 255]),
+    ];
+    // The last column is transparent padding outside the palette
+    let image = RgbaImage::from_fn(4, 2, |x, y| match x {
+        3 => Rgba([0, 0, 0, 0]),
+        _ => palette[((x + y) % 2) as usize],
+    });
+
+    let mut bytes = Vec::new();
+    write_indexed_png(&mut bytes, &image, &palette).unwrap();
+    let mut reader = png::Decoder::new(std::io::Cursor::new(&bytes))
+        .read_info()
+        .unwrap();
+    let info = reader.info();
+    assert_eq!(info.color_type, png::ColorType::Indexed);
+    let entries: Vec<u8> = palette
+        .iter()
+        .flat_map(|color| [color.0[0], color.0[1], color.0[2]])
+        .chain([0, 0, 0])
+        .collect();
+    assert_eq!(info.palette.as_deref(), Some(&entries[..]));
+    assert_eq!(info.trns.as_deref(), Some(&[255, 255, 255, 0][..]));
+
+    let mut indices = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut indices).unwrap();
+    assert_eq!(indices, [0, 1, 0, 3, 1, 0, 1, 3]);
+
+    // Decoding gives back the same image
+    assert_eq!(image::load_from_memory(&bytes).unwrap().to_rgba8(), image);
+
+    let noise = RgbaImage::from_fn(20, 20, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+    assert!(matches!(
+        write_indexed_png(&mut Vec::new(), &noise, &[]),
+        Err(PixelArtError::TooManyColors { colors: 400 })
+    ));
+}
+
+#[test]
+fn test_ramp_generator_dark_to_light_with_base_in_middle() {
+    let base = Rgba([60, 160, 60, 255]);
+    let ramp = RampGenerator::new(5).ramp(&base);
+
+    assert_eq!(ramp.len(), 5);
+    assert_eq!(ramp[2], base);
+    for pair in ramp.windows(2) {
+        assert!(rgba_to_lch(&pair[0]).l < rgba_to_lch(&pair[1]).l);
+    }
+}
+
+#[test]
+fn test_ramp_generator_hue_shift_direction() {
+    let base = Rgba([60, 160, 60, 255]); // green, hue 120
+    let shifted = RampGenerator::new(3).with_hue_shift(30.0).ramp(&base);
+    let flat = RampGenerator::new(3).with_hue_shift(0.0).ramp(&base);
+
+    let blue_bias = |c: &Rgba<u8>| c.0[2] as i32 - c.0[0] as i32;
+
+    // Shadows lean toward blue, highlights toward red/yellow
+    assert!(blue_bias(&shifted[0]) > blue_bias(&flat[0]));
+    assert!(blue_bias(&shifted[2]) < blue_bias(&flat[2]));
+}
+
+#[test]
+fn test_ramp_generator_saturation_curves() {
+    let base = Rgba([200, 80, 40, 255]);
+    let constant = RampGenerator::new(3)
+        .with_hue_shift(0.0)
+        .with_saturation_curve(SaturationCurve::Constant)
+        .ramp(&base);
+    let arc = RampGenerator::new(3)
+        .with_hue_shift(0.0)
+        .with_saturation_curve(SaturationCurve::Arc { falloff: 0.8 })
+        .ramp(&base);
+
+    assert_eq!(constant[1], arc[1]);
+    assert!(rgba_to_lch(&arc[2]).c < rgba_to_lch(&constant[2]).c);
+}
+
+#[test]
+fn test_ramp_palette_as_fixed_quantizer() {
+    let bases = vec![Rgba([200, 40, 40, 255]), Rgba([40, 40, 200, 255])];
+    let palette = RampGenerator::new(4).palette(&bases);
+    assert_eq!(palette.len(), 8);
+
+    let pixels = vec![Rgba([10, 200, 10, 255]); 4];
+    let built = PaletteQuantizer::fixed(palette.clone()).build_palette(&pixels);
+    assert_eq!(built, palette);
+}
+
+#[test]
+fn test_fixed_palette_with_duplicates_derives_nothing() {
+    use image::{DynamicImage, RgbaImage};
+    use pixel_art_rust::core::pixel_art::PixelArtConverter;
+
+    // Shadow steps of a black base all clamp to black, and the locked black repeats them
+    let black = Rgba([0, 0, 0, 255]);
+    let mut palette = vec![black];
+    palette.extend(RampGenerator::new(5).palette(&[black, Rgba([200, 40, 40, 255])]));
+    assert!(palette.iter().filter(|&&color| color == black).count() > 2);
+
+    let quantizer = PaletteQuantizer::fixed(palette.clone());
+    let image = RgbaImage::from_fn(32, 32, |x, y| Rgba([10, (x * 8) as u8, (y * 8) as u8, 255]));
+    let pixels: Vec<Rgba<u8>> = image.pixels().copied().collect();
+    assert!(
+        quantizer
+            .build_palette(&pixels)
+            .iter()
+            .all(|color| palette.contains(color))
+    );
+
+    let result = PixelArtConverter::builder()
+        .grid_cells(8, 8)
+        .palette(quantizer)
+        .build()
+        .unwrap()
+        .convert(&DynamicImage::ImageRgba8(image))
+        .unwrap()
+        .to_rgba8();
+    assert!(result.pixels().all(|pixel| palette.contains(pixel)));
+}
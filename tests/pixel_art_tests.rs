@@ -1,8 +1,11 @@
 use image::{DynamicImage, Rgba, RgbaImage};
 use pixel_art_rust::core::color::AverageColorExtractor;
-use pixel_art_rust::core::grid::Grid;
+use pixel_art_rust::core::dither::Dithering;
+use pixel_art_rust::core::error::PixelArtError;
+use pixel_art_rust::core::grid::{Grid, PixelAspect, Rect};
 use pixel_art_rust::core::pixel_art::*;
-use pixel_art_rust::core::quadtree::QuadTree;
+use pixel_art_rust::core::quadtree::QuadTreeConfig;
+use pixel_art_rust::core::quantization::{PaletteQuantizer, QuantizationMethod};
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -185,10 +188,9 @@ fn test_adaptive_resolution() {
 #[test]
 fn test_processing_strategy_enum() {
     let grid = Grid::new(4, 4, 2, 2);
-    let quadtree = QuadTree::build(&RgbaImage::new(4, 4), 3, 50.0);
 
-    let grid_strategy = ProcessingStrategy::UniformGrid(grid);
-    let tree_strategy = ProcessingStrategy::AdaptiveQuadTree(quadtree);
+    let grid_strategy = ProcessingStrategy::UniformGrid(grid.into());
+    let tree_strategy = ProcessingStrategy::AdaptiveQuadTree(QuadTreeConfig::new(3, 50.0));
 
     // Test pattern matching
     match grid_strategy {
@@ -516,3 +518,523 @@ fn test_progress_callback() {
     // Note: This test won't work as written because progress_calls is moved
     // This is more of a compilation test to ensure the API works
 }
+
+#[test]
+fn test_palette_quantizer_limits_output_colors() {
+    // A gradient with a near-magenta corner for the locked color to take over
+    let mut image = RgbaImage::new(16, 16);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        *pixel = if x < 4 && y < 4 {
+            Rgba([250, 10, 245, 255])
+        } else {
+            Rgba([(x * 16) as u8, (y * 16) as u8, 64, 255])
+        };
+    }
+
+    let grid = Grid::new(16, 16, 8, 8);
+    let mut converter = PixelArtConverter::with_grid(grid, Box::new(AverageColorExtractor));
+    converter.set_palette_quantizer(
+        PaletteQuantizer::new(QuantizationMethod::MedianCut, 4)
+            .with_locked_colors(vec![Rgba([255, 0, 255, 255])]),
+    );
+
+    let result = converter
+        .convert_parallel(&DynamicImage::ImageRgba8(image))
+        .unwrap()
+        .to_rgba8();
+
+    let unique: std::collections::HashSet<Rgba<u8>> = result.pixels().copied().collect();
+    assert!(unique.len() <= 4);
+    assert!(unique.contains(&Rgba([255, 0, 255, 255])));
+    assert_eq!(*result.get_pixel(0, 0), Rgba([255, 0, 255, 255]));
+}
+
+#[test]
+fn test_merge_uniform_matches_plain_grid() {
+    let mut image = RgbaImage::new(53, 37);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        *pixel = if x < 30 && y < 20 {
+            Rgba([40, 120, 200, 255])
+        } else if y > 28 {
+            Rgba([200, 60, 10, 255])
+        } else {
+            Rgba([
+                (x * 13 % 256) as u8,
+                (y * 29 % 256) as u8,
+                ((x ^ y) * 7) as u8,
+                255,
+            ])
+        };
+    }
+    let dynamic_image = DynamicImage::ImageRgba8(image);
+
+    for (columns, rows) in [(10, 7), (53, 37), (4, 3)] {
+        let grid = Grid::new(53, 37, columns, rows);
+        let plain = PixelArtConverter::with_grid(grid.clone(), Box::new(AverageColorExtractor));
+        let mut merged = PixelArtConverter::with_grid(grid, Box::new(AverageColorExtractor));
+        merged.set_merge_uniform(true);
+
+        let expected = plain.convert(&dynamic_image).unwrap().to_rgba8();
+        assert_eq!(merged.convert(&dynamic_image).unwrap().to_rgba8(), expected);
+        assert_eq!(
+            merged.convert_parallel(&dynamic_image).unwrap().to_rgba8(),
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_merge_uniform_reports_every_cell() {
+    let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(16, 16, Rgba([9, 9, 9, 255])));
+    let grid = Grid::new(16, 16, 8, 8);
+    let mut converter = PixelArtConverter::with_grid(grid, Box::new(AverageColorExtractor));
+    converter.set_merge_uniform(true);
+
+    let counter = Arc::new(AtomicU32::new(0));
+    let counter_clone = Arc::clone(&counter);
+    converter.set_progress_callback(Arc::new(move |_row, _col| {
+        counter_clone.fetch_add(1, Ordering::Relaxed);
+    }));
+
+    converter.convert_parallel(&image).unwrap();
+    assert_eq!(counter.load(Ordering::Relaxed), 64);
+}
+
+#[test]
+fn test_quadtree_progress_reports_pixels() {
+    let mut image = RgbaImage::new(24, 16);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        *pixel = Rgba([(x * 10) as u8, (y * 15) as u8, ((x * y) % 256) as u8, 255]);
+    }
+
+    let mut converter = PixelArtConverter::with_quadtree(5, 10.0, Box::new(AverageColorExtractor));
+    let updates = Arc::new(Mutex::new(Vec::new()));
+    let updates_clone = Arc::clone(&updates);
+    converter.set_tree_progress_callback(Arc::new(move |done, total| {
+        updates_clone.lock().unwrap().push((done, total));
+    }));
+    // Grid callbacks have no meaning for a tree and must not be called
+    converter.set_progress_callback(Arc::new(|row, col| {
+        panic!("unexpected grid progress ({row}, {col})");
+    }));
+
+    let dynamic_image = DynamicImage::ImageRgba8(image);
+    converter.convert_parallel(&dynamic_image).unwrap();
+
+    let updates = updates.lock().unwrap();
+    assert!(updates.len() > 1);
+    assert!(
+        updates
+            .iter()
+            .all(|&(done, total)| total == 24 * 16 && done <= total)
+    );
+    assert_eq!(updates.iter().map(|&(done, _)| done).max(), Some(24 * 16));
+}
+
+#[test]
+fn test_parallel_fill_matches_sequential_with_remainder() {
+    // 37x23 on a 5x4 grid leaves 2 columns and 3 rows uncovered
+    let image = RgbaImage::from_fn(37, 23, |x, y| {
+        Rgba([(x * 7) as u8, (y * 11) as u8, ((x + y) * 3) as u8, 255])
+    });
+    let grid = Grid::new(37, 23, 5, 4);
+    let converter = PixelArtConverter::with_grid(grid.clone(), Box::new(AverageColorExtractor));
+    let dynamic_image = DynamicImage::ImageRgba8(image);
+
+    let sequential = converter.convert(&dynamic_image).unwrap().to_rgba8();
+    let parallel = converter
+        .convert_parallel(&dynamic_image)
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(sequential, parallel);
+
+    let (cell_width, cell_height) = (grid.cell_width(), grid.cell_height());
+    for (x, y, pixel) in parallel.enumerate_pixels() {
+        if x >= 5 * cell_width || y >= 4 * cell_height {
+            assert_eq!(*pixel, Rgba([0, 0, 0, 0]));
+        } else {
+            let (row, col) = (y / cell_height, x / cell_width);
+            assert_eq!(
+                pixel,
+                parallel.get_pixel(col * cell_width, row * cell_height)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_conversion_errors_are_typed() {
+    let extractor = || Box::new(AverageColorExtractor);
+    let image = DynamicImage::ImageRgba8(RgbaImage::new(8, 8));
+
+    let converter = PixelArtConverter::with_grid(Grid::new(8, 8, 4, 4), extractor());
+    let empty = DynamicImage::ImageRgba8(RgbaImage::new(0, 8));
+    assert!(matches!(
+        converter.convert(&empty),
+        Err(PixelArtError::ZeroSizedImage {
+            width: 0,
+            height: 8
+        })
+    ));
+
+    let converter = PixelArtConverter::with_grid(Grid::new(8, 8, 0, 4), extractor());
+    assert!(matches!(
+        converter.convert(&image),
+        Err(PixelArtError::InvalidGrid {
+            columns: 0,
+            rows: 4
+        })
+    ));
+
+    let converter = PixelArtConverter::with_grid(Grid::new(8, 8, 16, 4), extractor());
+    assert!(matches!(
+        converter.convert_parallel(&image),
+        Err(PixelArtError::GridLargerThanImage { columns: 16, .. })
+    ));
+
+    let converter = PixelArtConverter::with_grid(Grid::new(8, 8, 4, 4), extractor());
+    let small = DynamicImage::ImageRgba8(RgbaImage::new(3, 8));
+    assert!(matches!(
+        converter.convert(&small),
+        Err(PixelArtError::GridLargerThanImage { width: 3, .. })
+    ));
+}
+
+#[test]
+fn test_builder_resolves_grid_per_image() {
+    let converter = PixelArtConverter::builder()
+        .grid_cells(4, 4)
+        .extractor(AverageColorExtractor)
+        .build()
+        .unwrap();
+
+    for (width, height) in [(16, 16), (40, 24)] {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x * 6) as u8, (y * 9) as u8, 100, 255])
+        }));
+        let expected = PixelArtConverter::with_grid(
+            Grid::new(width, height, 4, 4),
+            Box::new(AverageColorExtractor),
+        )
+        .convert(&image)
+        .unwrap();
+        assert_eq!(converter.convert_parallel(&image).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_builder_cell_size_and_scale() {
+    let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(20, 12, |x, y| {
+        Rgba([(x * 12) as u8, (y * 20) as u8, 0, 255])
+    }));
+
+    let converter = PixelArtConverter::builder()
+        .cell_size(4, 3)
+        .scale(2)
+        .build()
+        .unwrap();
+    assert_eq!(converter.output_dimensions(20, 12).unwrap(), (10, 8));
+
+    let native = PixelArtConverter::builder()
+        .cell_size(4, 3)
+        .scale(1)
+        .build()
+        .unwrap()
+        .convert(&image)
+        .unwrap()
+        .to_rgba8();
+    let scaled = converter.convert(&image).unwrap().to_rgba8();
+    assert_eq!(native.dimensions(), (5, 4));
+    assert_eq!(scaled.dimensions(), (10, 8));
+    for (x, y, pixel) in scaled.enumerate_pixels() {
+        assert_eq!(pixel, native.get_pixel(x / 2, y / 2));
+    }
+
+    // Cells larger than the image can't be placed
+    assert!(matches!(
+        converter.convert(&DynamicImage::ImageRgba8(RgbaImage::new(3, 3))),
+        Err(PixelArtError::CellLargerThanImage { .. })
+    ));
+}
+
+#[test]
+fn test_builder_dithering_uses_palette_colors_only() {
+    let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(32, 32, |x, _| {
+        Rgba([(x * 8) as u8, (x * 8) as u8, (x * 8) as u8, 255])
+    }));
+    let palette = vec![Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255])];
+    let converter = PixelArtConverter::builder()
+        .grid_cells(16, 16)
+        .palette(PaletteQuantizer::fixed(palette.clone()))
+        .dithering(Dithering::FloydSteinberg)
+        .build()
+        .unwrap();
+
+    let result = converter.convert(&image).unwrap().to_rgba8();
+    assert!(result.pixels().all(|pixel| palette.contains(pixel)));
+    // The gradient's middle columns mix both colors
+    let middle: Vec<_> = (0..32).map(|y| *result.get_pixel(16, y)).collect();
+    assert!(middle.iter().any(|pixel| *pixel == palette[0]));
+    assert!(middle.iter().any(|pixel| *pixel == palette[1]));
+}
+
+#[test]
+fn test_builder_rejects_conflicting_options() {
+    let invalid = |builder: PixelArtConverterBuilder| {
+        matches!(builder.build(), Err(PixelArtError::InvalidConfig(_)))
+    };
+    let quadtree = || PixelArtConverter::builder().quadtree(QuadTreeConfig::new(4, 10.0));
+
+    assert!(invalid(PixelArtConverter::builder()));
+    assert!(invalid(
+        PixelArtConverter::builder().grid_cells(4, 4).scale(0)
+    ));
+    assert!(invalid(
+        PixelArtConverter::builder()
+            .grid_cells(4, 4)
+            .dithering(Dithering::Ordered)
+    ));
+    assert!(invalid(quadtree().scale(2)));
+    assert!(invalid(quadtree().merge_uniform(true)));
+    assert!(matches!(
+        PixelArtConverter::builder().grid_cells(0, 4).build(),
+        Err(PixelArtError::InvalidGrid { .. })
+    ));
+    assert!(matches!(
+        PixelArtConverter::builder().cell_size(8, 0).build(),
+        Err(PixelArtError::InvalidCellSize { .. })
+    ));
+    assert!(quadtree().build().is_ok());
+}
+
+#[test]
+fn test_converter_is_shared_across_threads_and_sizes() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<PixelArtConverter>();
+
+    // Built for a 16x16 image, then used on other sizes from several threads
+    let converter = Arc::new(PixelArtConverter::with_grid(
+        Grid::new(16, 16, 4, 4),
+        Box::new(AverageColorExtractor),
+    ));
+    let handles: Vec<_> = [(16, 16), (48, 20), (9, 31)]
+        .into_iter()
+        .map(|(width, height)| {
+            let converter = Arc::clone(&converter);
+            thread::spawn(move || {
+                let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+                    Rgba([(x * 5) as u8, (y * 7) as u8, 50, 255])
+                }));
+                let result = converter.convert(&image).unwrap().to_rgba8();
+                let expected = PixelArtConverter::with_grid(
+                    Grid::new(width, height, 4, 4),
+                    Box::new(AverageColorExtractor),
+                )
+                .convert(&image)
+                .unwrap()
+                .to_rgba8();
+                assert_eq!(result.dimensions(), (width, height));
+                assert_eq!(result, expected);
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn test_grid_offset_aligns_upscaled_pixel_art() {
+    // A 4x3 sprite upscaled 5x, shifted by (2, 1) so cells no longer start at the origin
+    let sprite = RgbaImage::from_fn(4, 3, |x, y| Rgba([(x * 60) as u8, (y * 80) as u8, 90, 255]));
+    let image = RgbaImage::from_fn(22, 16, |x, y| {
+        if x < 2 || y < 1 {
+            Rgba([255, 255, 255, 255])
+        } else {
+            *sprite.get_pixel(((x - 2) / 5).min(3), ((y - 1) / 5).min(2))
+        }
+    });
+    let image = DynamicImage::ImageRgba8(image);
+
+    let native = PixelArtConverter::builder()
+        .cell_size(5, 5)
+        .offset(2, 1)
+        .scale(1)
+        .build()
+        .unwrap();
+    assert_eq!(native.output_dimensions(22, 16).unwrap(), (4, 3));
+    assert_eq!(native.convert(&image).unwrap().to_rgba8(), sprite);
+
+    // Unscaled, the output keeps the input size and the skipped pixels stay transparent
+    let framed = PixelArtConverter::builder()
+        .cell_size(5, 5)
+        .offset(2, 1)
+        .build()
+        .unwrap()
+        .convert(&image)
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(framed.dimensions(), (22, 16));
+    assert_eq!(*framed.get_pixel(1, 5), Rgba([0, 0, 0, 0]));
+    assert_eq!(*framed.get_pixel(5, 0), Rgba([0, 0, 0, 0]));
+    assert_eq!(framed.get_pixel(2, 1), sprite.get_pixel(0, 0));
+    assert_eq!(framed.get_pixel(21, 15), sprite.get_pixel(3, 2));
+}
+
+#[test]
+fn test_crop_limits_conversion_to_region() {
+    let image = RgbaImage::from_fn(40, 30, |x, y| {
+        if (10..30).contains(&x) && (10..20).contains(&y) {
+            Rgba([0, 200, 0, 255])
+        } else {
+            Rgba([200, 0, 0, 255])
+        }
+    });
+    let image = DynamicImage::ImageRgba8(image);
+
+    let converter = PixelArtConverter::builder()
+        .grid_cells(4, 2)
+        .crop(Rect::new(10, 10, 20, 10))
+        .build()
+        .unwrap();
+    assert_eq!(converter.output_dimensions(40, 30).unwrap(), (20, 10));
+    let result = converter.convert_parallel(&image).unwrap().to_rgba8();
+    assert_eq!(result.dimensions(), (20, 10));
+    assert!(
+        result
+            .pixels()
+            .all(|pixel| *pixel == Rgba([0, 200, 0, 255]))
+    );
+
+    // Adaptive mode works on the crop as well
+    let tree = PixelArtConverter::builder()
+        .quadtree(QuadTreeConfig::new(4, 10.0))
+        .crop(Rect::new(10, 10, 20, 10))
+        .build()
+        .unwrap();
+    let result = tree.convert(&image).unwrap().to_rgba8();
+    assert_eq!(result.dimensions(), (20, 10));
+    assert!(
+        result
+            .pixels()
+            .all(|pixel| *pixel == Rgba([0, 200, 0, 255]))
+    );
+
+    assert!(matches!(
+        converter.convert(&DynamicImage::ImageRgba8(RgbaImage::new(25, 25))),
+        Err(PixelArtError::RegionOutsideImage { .. })
+    ));
+    assert!(matches!(
+        PixelArtConverter::builder()
+            .grid_cells(4, 4)
+            .crop(Rect::new(0, 0, 0, 10))
+            .build(),
+        Err(PixelArtError::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn test_pixel_aspect_renders_wide_pixels() {
+    let image = RgbaImage::from_fn(320, 200, |x, y| {
+        Rgba([(x * 255 / 319) as u8, (y * 255 / 199) as u8, 128, 255])
+    });
+    let image = DynamicImage::ImageRgba8(image);
+
+    // 160 columns of 2:1 pixels, each drawn as a 4x2 block
+    let converter = PixelArtConverter::builder()
+        .target_width(160)
+        .pixel_aspect(PixelAspect::new(2, 1))
+        .scale(2)
+        .build()
+        .unwrap();
+    assert_eq!(converter.output_dimensions(320, 200).unwrap(), (640, 400));
+    let result = converter.convert_parallel(&image).unwrap().to_rgba8();
+    assert_eq!(result.dimensions(), (640, 400));
+    for (x, y, pixel) in result.enumerate_pixels() {
+        assert_eq!(pixel, result.get_pixel(x / 4 * 4, y / 2 * 2), "({x}, {y})");
+    }
+    assert_ne!(result.get_pixel(0, 0), result.get_pixel(4, 0));
+    assert_ne!(result.get_pixel(0, 0), result.get_pixel(0, 2));
+
+    assert!(matches!(
+        PixelArtConverter::builder()
+            .grid_cells(4, 4)
+            .pixel_aspect(PixelAspect::new(0, 1))
+            .build(),
+        Err(PixelArtError::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        PixelArtConverter::builder()
+            .quadtree(QuadTreeConfig::new(4, 10.0))
+            .pixel_aspect(PixelAspect::new(2, 1))
+            .build(),
+        Err(PixelArtError::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn test_palette_callback_reports_ordered_palette() {
+    use pixel_art_rust::core::palette::{PaletteOrder, rgba_to_lch};
+
+    let image = RgbaImage::from_fn(32, 32, |x, y| Rgba([(x * 8) as u8, (y * 8) as u8, 90, 255]));
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&reported);
+    let converter = PixelArtConverter::builder()
+        .grid_cells(8, 8)
+        .palette(
+            PaletteQuantizer::new(QuantizationMethod::MedianCut, 6)
+                .with_order(PaletteOrder::Luminance),
+        )
+        .palette_callback(Arc::new(move |palette| {
+            *sink.lock().unwrap() = palette.to_vec();
+        }))
+        .build()
+        .unwrap();
+    let result = converter
+        .convert(&DynamicImage::ImageRgba8(image))
+        .unwrap()
+        .to_rgba8();
+
+    let palette = reported.lock().unwrap().clone();
+    assert!(!palette.is_empty() && palette.len() <= 6);
+    for pair in palette.windows(2) {
+        assert!(rgba_to_lch(&pair[0]).l <= rgba_to_lch(&pair[1]).l);
+    }
+    assert!(result.pixels().all(|pixel| palette.contains(pixel)));
+}
+
+#[test]
+fn test_conversion_returns_the_tree_it_rendered() {
+    let image = RgbaImage::from_fn(40, 30, |x, y| {
+        Rgba([if x < 20 { 30 } else { 220 }, (y * 8) as u8, 90, 255])
+    });
+    let image = DynamicImage::ImageRgba8(image);
+
+    let converter = PixelArtConverter::builder()
+        .quadtree(QuadTreeConfig::new(5, 20.0))
+        .offset(3, 2)
+        .build()
+        .unwrap();
+    let (result, tree) = converter.convert_parallel_with_tree(&image).unwrap();
+    let (result, tree) = (result.to_rgba8(), tree.unwrap());
+    assert_eq!(
+        result,
+        converter.convert_parallel(&image).unwrap().to_rgba8()
+    );
+
+    // Leaves are relative to the area after the offset
+    assert!(tree.leaves().len() > 1);
+    for leaf in tree.leaves() {
+        let color = tree.leaf_color(leaf);
+        assert_eq!(*result.get_pixel(leaf.x + 3, leaf.y + 2), color);
+        let (right, bottom) = (leaf.x + leaf.width + 2, leaf.y + leaf.height + 1);
+        assert_eq!(*result.get_pixel(right, bottom), color);
+    }
+
+    let grid = PixelArtConverter::builder()
+        .grid_cells(4, 3)
+        .build()
+        .unwrap();
+    assert!(grid.convert_parallel_with_tree(&image).unwrap().1.is_none());
+}
@@ -1,5 +1,6 @@
 use image::{Rgba, RgbaImage};
 use pixel_art_rust::core::quadtree::*;
+use std::sync::{Arc, Mutex};
 
 #[test]
 fn test_quadtree_creation() {
@@ -15,7 +16,8 @@ fn test_quadtree_creation() {
     // Root should exist and have the correct properties
     assert_eq!(tree.root.x, 0);
     assert_eq!(tree.root.y, 0);
-    assert_eq!(tree.root.size, 4);
+    assert_eq!(tree.root.width, 4);
+    assert_eq!(tree.root.height, 4);
     assert_eq!(tree.root.mean_color, Rgba([128, 128, 128, 255]));
     assert!(tree.root.variance < 10.0); // Should be low variance
     assert!(tree.root.children.is_none()); // Should not split uniform colors
@@ -74,6 +76,58 @@ fn test_quadtree_quantization() {
     assert!(has_palette_indices);
 }
 
+#[test]
+fn test_quadtree_palette_respects_size_and_weights() {
+    // A large red area and two small blue/green quadrants
+    let mut image = RgbaImage::new(8, 8);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        *pixel = match (x >= 4, y >= 4) {
+            (true, true) if x >= 6 => Rgba([0, 0, 255, 255]),
+            (true, true) => Rgba([0, 255, 0, 255]),
+            _ => Rgba([255, 0, 0, 255]),
+        };
+    }
+
+    let mut tree = QuadTree::build(&image, 4, 10.0);
+    let palette = tree.quantize_with_palette(2).to_vec();
+    assert_eq!(palette.len(), 2);
+    assert!(palette.contains(&Rgba([255, 0, 0, 255])));
+
+    for leaf in tree.leaves() {
+        let idx = leaf.palette_idx.expect("every leaf has an index") as usize;
+        assert!(idx < palette.len());
+        assert_eq!(
+            idx,
+            pixel_art_rust::core::quantization::nearest_palette_index(&leaf.mean_color, &palette)
+        );
+    }
+}
+
+#[test]
+fn test_quadtree_index_map_matches_leaves() {
+    let mut image = RgbaImage::new(6, 4);
+    for (x, _, pixel) in image.enumerate_pixels_mut() {
+        *pixel = if x < 3 {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        };
+    }
+
+    let mut tree = QuadTree::build(&image, 3, 1.0);
+    assert!(tree.index_map().is_none());
+
+    tree.quantize_with_palette(2);
+    let indices = tree.index_map().unwrap();
+    assert_eq!(indices.len(), 24);
+    for y in 0..4 {
+        for x in 0..6 {
+            let color = tree.palette()[indices[y * 6 + x] as usize];
+            assert_eq!(color, *image.get_pixel(x as u32, y as u32));
+        }
+    }
+}
+
 #[test]
 fn test_quadtree_to_grid_cells() {
     let mut image = RgbaImage::new(2, 2);
@@ -99,11 +153,12 @@ fn test_quadtree_to_grid_cells() {
 
 #[test]
 fn test_quad_node_creation() {
-    let node = QuadNode::new(0, 0, 4, Rgba([128, 128, 128, 255]), 25.0);
+    let node = QuadNode::new(0, 0, 4, 4, Rgba([128, 128, 128, 255]), 25.0);
 
     assert_eq!(node.x, 0);
     assert_eq!(node.y, 0);
-    assert_eq!(node.size, 4);
+    assert_eq!(node.width, 4);
+    assert_eq!(node.height, 4);
     assert_eq!(node.mean_color, Rgba([128, 128, 128, 255]));
     assert_eq!(node.variance, 25.0);
     assert!(node.palette_idx.is_none());
@@ -112,13 +167,13 @@ fn test_quad_node_creation() {
 
 #[test]
 fn test_quad_node_splitting() {
-    let mut node = QuadNode::new(0, 0, 4, Rgba([128, 128, 128, 255]), 25.0);
+    let mut node = QuadNode::new(0, 0, 4, 4, Rgba([128, 128, 128, 255]), 25.0);
 
     // Create child nodes
-    let child1 = QuadNode::new(0, 0, 2, Rgba([255, 0, 0, 255]), 10.0);
-    let child2 = QuadNode::new(2, 0, 2, Rgba([0, 255, 0, 255]), 10.0);
-    let child3 = QuadNode::new(0, 2, 2, Rgba([0, 0, 255, 255]), 10.0);
-    let child4 = QuadNode::new(2, 2, 2, Rgba([255, 255, 0, 255]), 10.0);
+    let child1 = QuadNode::new(0, 0, 2, 2, Rgba([255, 0, 0, 255]), 10.0);
+    let child2 = QuadNode::new(2, 0, 2, 2, Rgba([0, 255, 0, 255]), 10.0);
+    let child3 = QuadNode::new(0, 2, 2, 2, Rgba([0, 0, 255, 255]), 10.0);
+    let child4 = QuadNode::new(2, 2, 2, 2, Rgba([255, 255, 0, 255]), 10.0);
 
     node.children = Some(Box::new([child1, child2, child3, child4]));
 
@@ -135,11 +190,12 @@ fn test_image_region_creation() {
         *pixel = Rgba([x as u8 * 64, y as u8 * 64, 128, 255]);
     }
 
-    let region = ImageRegion::new(&image, 0, 0, 4);
+    let region = ImageRegion::new(&image, 0, 0, 4, 4);
 
     assert_eq!(region.x, 0);
     assert_eq!(region.y, 0);
-    assert_eq!(region.size, 4);
+    assert_eq!(region.width, 4);
+    assert_eq!(region.height, 4);
     assert!(!region.pixels.is_empty());
 }
 
@@ -160,8 +216,8 @@ fn test_variance_calculation() {
 
 #[test]
 fn test_should_split_decision() {
-    let high_variance_node = QuadNode::new(0, 0, 4, Rgba([128, 128, 128, 255]), 100.0);
-    let low_variance_node = QuadNode::new(0, 0, 4, Rgba([128, 128, 128, 255]), 5.0);
+    let high_variance_node = QuadNode::new(0, 0, 4, 4, Rgba([128, 128, 128, 255]), 100.0);
+    let low_variance_node = QuadNode::new(0, 0, 4, 4, Rgba([128, 128, 128, 255]), 5.0);
 
     assert!(should_split_node(&high_variance_node, 2, 50.0));
     assert!(!should_split_node(&low_variance_node, 2, 50.0));
@@ -220,3 +276,289 @@ fn test_adaptive_progress_calculation() {
         assert!(progress_percent <= 100);
     }
 }
+
+#[test]
+fn test_quadtree_tiles_non_square_image_exactly() {
+    for (width, height) in [(7, 5), (13, 4), (3, 9), (1, 6)] {
+        let mut image = RgbaImage::new(width, height);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            *pixel = Rgba([(x * 37 % 256) as u8, (y * 53 % 256) as u8, 0, 255]);
+        }
+
+        let tree = QuadTree::build(&image, 6, 0.0);
+        assert_eq!((tree.root.width, tree.root.height), (width, height));
+
+        let mut coverage = vec![0u32; (width * height) as usize];
+        for (x, y, w, h, _color) in tree.to_grid_cells() {
+            assert!(w > 0 && h > 0);
+            assert!(x + w <= width && y + h <= height);
+            for py in y..y + h {
+                for px in x..x + w {
+                    coverage[(py * width + px) as usize] += 1;
+                }
+            }
+        }
+        assert!(coverage.iter().all(|&count| count == 1));
+    }
+}
+
+#[test]
+fn test_split_bounds_odd_sizes() {
+    let quadrants = split_bounds(2, 3, 5, 3);
+
+    assert_eq!(quadrants[0], (2, 3, 2, 1));
+    assert_eq!(quadrants[1], (4, 3, 3, 1));
+    assert_eq!(quadrants[2], (2, 4, 2, 2));
+    assert_eq!(quadrants[3], (4, 4, 3, 2));
+}
+
+#[test]
+fn test_parallel_build_matches_sequential() {
+    let mut image = RgbaImage::new(97, 61);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        *pixel = Rgba([
+            ((x * x + y) % 256) as u8,
+            ((y * 7) % 256) as u8,
+            ((x ^ y) % 256) as u8,
+            255,
+        ]);
+    }
+
+    let sequential = QuadTree::build(&image, 6, 30.0);
+    for cutoff in [1, 64, DEFAULT_PARALLEL_CUTOFF] {
+        let parallel = QuadTree::build_parallel(&image, 6, 30.0, cutoff);
+
+        assert_eq!(parallel.node_count(), sequential.node_count());
+        assert_eq!(parallel.to_grid_cells(), sequential.to_grid_cells());
+    }
+}
+
+#[test]
+fn test_lattice_leaves_are_cell_multiples() {
+    let mut image = RgbaImage::new(50, 35);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        *pixel = if x < 25 {
+            Rgba([30, 30, 30, 255])
+        } else {
+            Rgba([(x * 41 % 256) as u8, (y * 23 % 256) as u8, 90, 255])
+        };
+    }
+
+    let config = QuadTreeConfig::new(8, 5.0).with_cell_size(5, 7);
+    let tree = QuadTree::build_with_config(&image, &config);
+
+    assert_eq!((tree.root.width, tree.root.height), (50, 35));
+    let cells = tree.to_grid_cells();
+    assert!(cells.len() > 1);
+    for (x, y, w, h, _color) in cells {
+        assert_eq!(x % 5, 0);
+        assert_eq!(y % 7, 0);
+        assert_eq!(w % 5, 0);
+        assert_eq!(h % 7, 0);
+        assert!(w > 0 && h > 0);
+    }
+}
+
+#[test]
+fn test_quadtree_json_export() {
+    let mut image = RgbaImage::new(4, 4);
+    for (x, _, pixel) in image.enumerate_pixels_mut() {
+        *pixel = if x < 2 {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        };
+    }
+
+    let tree = QuadTree::build(&image, 2, 1.0);
+    let json: serde_json::Value = serde_json::from_str(&tree.to_json().unwrap()).unwrap();
+
+    assert_eq!(json["node_count"], tree.node_count());
+    assert_eq!(json["root"]["width"], 4);
+    assert_eq!(json["root"]["depth"], 0);
+    let children = json["root"]["children"].as_array().unwrap();
+    assert_eq!(children.len(), 4);
+    assert_eq!(children[1]["depth"], 1);
+    assert_eq!(
+        children[1]["color"],
+        serde_json::json!([255, 255, 255, 255])
+    );
+    assert!(children[0].get("children").is_none());
+}
+
+#[test]
+fn test_quadtree_overlay_draws_depth_colored_borders() {
+    let mut image = RgbaImage::new(8, 8);
+    for (x, _, pixel) in image.enumerate_pixels_mut() {
+        *pixel = if x < 4 {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        };
+    }
+
+    let tree = QuadTree::build(&image, 1, 1.0);
+    let overlay = tree.render_overlay(&image);
+
+    assert_eq!(overlay.dimensions(), image.dimensions());
+    // Child borders are drawn over the root border
+    assert_eq!(*overlay.get_pixel(0, 0), DEPTH_COLORS[1]);
+    assert_eq!(*overlay.get_pixel(3, 1), DEPTH_COLORS[1]);
+    // Interior pixels keep the base image
+    assert_eq!(*overlay.get_pixel(1, 1), Rgba([0, 0, 0, 255]));
+    assert_eq!(*overlay.get_pixel(5, 5), Rgba([255, 255, 255, 255]));
+}
+
+fn corner_detail_image() -> RgbaImage {
+    // Flat image with a checkerboard in the top-left 4x4 corner
+    let mut image = RgbaImage::from_pixel(32, 32, Rgba([100, 100, 100, 255]));
+    for y in 0..4 {
+        for x in 0..4 {
+            if (x + y) % 2 == 0 {
+                image.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+    }
+    image
+}
+
+fn max_neighbor_depth_gap(tree: &QuadTree) -> u32 {
+    // Power-of-two image, so a leaf's depth follows from its width
+    let depth = |node: &QuadNode| 32u32.ilog2() - node.width.ilog2();
+    tree.leaves()
+        .into_iter()
+        .flat_map(|leaf| {
+            tree.all_neighbors(leaf)
+                .into_iter()
+                .map(move |neighbor| depth(neighbor).abs_diff(depth(leaf)))
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[test]
+fn test_quadtree_neighbors() {
+    let mut image = RgbaImage::new(4, 4);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        *pixel = Rgba([(x * 60) as u8, (y * 60) as u8, 0, 255]);
+    }
+    let tree = QuadTree::build(&image, 1, 1.0);
+
+    let top_left = tree.leaf_at(0, 0).unwrap();
+    assert!(tree.neighbors(top_left, Direction::North).is_empty());
+    assert!(tree.neighbors(top_left, Direction::West).is_empty());
+
+    let east = tree.neighbors(top_left, Direction::East);
+    assert_eq!(east.len(), 1);
+    assert_eq!((east[0].x, east[0].y), (2, 0));
+
+    let south = tree.neighbors(top_left, Direction::South);
+    assert_eq!(south.len(), 1);
+    assert_eq!((south[0].x, south[0].y), (0, 2));
+
+    assert_eq!(tree.all_neighbors(top_left).len(), 2);
+    assert!(tree.leaf_at(4, 0).is_none());
+}
+
+#[test]
+fn test_quadtree_neighbors_across_levels() {
+    let tree = QuadTree::build(&corner_detail_image(), 5, 1.0);
+
+    // The flat top-right quadrant borders several small leaves on its west side
+    let top_right = tree.leaf_at(16, 0).unwrap();
+    assert_eq!(top_right.width, 16);
+    let west = tree.neighbors(top_right, Direction::West);
+    assert!(west.len() > 1);
+    assert_eq!(west.iter().map(|leaf| leaf.height).sum::<u32>(), 16);
+}
+
+#[test]
+fn test_quadtree_balance_limits_depth_gap() {
+    let image = corner_detail_image();
+
+    let unbalanced = QuadTree::build(&image, 5, 1.0);
+    assert!(max_neighbor_depth_gap(&unbalanced) > 1);
+
+    let config = QuadTreeConfig::new(5, 1.0).with_balance(true);
+    let balanced = QuadTree::build_with_config(&image, &config);
+    assert!(max_neighbor_depth_gap(&balanced) <= 1);
+    assert!(balanced.node_count() > unbalanced.node_count());
+
+    // Balancing only adds splits, so the leaves still tile the image
+    let area: u32 = balanced
+        .leaves()
+        .iter()
+        .map(|leaf| leaf.width * leaf.height)
+        .sum();
+    assert_eq!(area, 32 * 32);
+}
+
+#[test]
+fn test_quadtree_progress_counts_covered_pixels() {
+    let mut image = RgbaImage::new(21, 13);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        *pixel = Rgba([(x * 12) as u8, (y * 19) as u8, 0, 255]);
+    }
+
+    let last = Arc::new(Mutex::new((0u64, 0u64)));
+    let last_clone = Arc::clone(&last);
+    let config = QuadTreeConfig::new(6, 1.0)
+        .with_cell_size(4, 3)
+        .with_parallel_cutoff(1)
+        .with_progress(Arc::new(move |done, total| {
+            let mut last = last_clone.lock().unwrap();
+            *last = (last.0.max(done), total);
+        }));
+    QuadTree::build_with_config(&image, &config);
+
+    // Only whole 4x3 cells are covered: 5 columns x 4 rows
+    assert_eq!(*last.lock().unwrap(), (20 * 12, 20 * 12));
+}
+
+#[test]
+fn test_progress_is_monotonic_and_throttled() {
+    use pixel_art_rust::core::progress::ProgressTracker;
+
+    let updates = Arc::new(Mutex::new(Vec::new()));
+    let updates_clone = Arc::clone(&updates);
+    let tracker = ProgressTracker::new(
+        400_000,
+        Arc::new(move |done, _total| updates_clone.lock().unwrap().push(done)),
+    );
+    std::thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                for _ in 0..50_000 {
+                    tracker.advance(1);
+                }
+            });
+        }
+    });
+
+    let updates = updates.lock().unwrap();
+    assert!(updates.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(updates.last(), Some(&400_000));
+    // About one call per thousandth of the work, not one per unit
+    assert!(updates.len() <= 1001, "{} calls", updates.len());
+
+    // Rayon workers finishing quadtree leaves out of order never move progress back
+    let image = RgbaImage::from_fn(256, 256, |x, y| {
+        Rgba([
+            ((x * 37) ^ (y * 11)) as u8,
+            (y * 53) as u8,
+            (x * y) as u8,
+            255,
+        ])
+    });
+    let leaves = Arc::new(Mutex::new(Vec::new()));
+    let leaves_clone = Arc::clone(&leaves);
+    let config = QuadTreeConfig::new(8, 1.0)
+        .with_parallel_cutoff(1)
+        .with_progress(Arc::new(move |done, _total| {
+            leaves_clone.lock().unwrap().push(done)
+        }));
+    QuadTree::build_with_config(&image, &config);
+    let leaves = leaves.lock().unwrap();
+    assert!(leaves.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(leaves.last(), Some(&(256 * 256)));
+}
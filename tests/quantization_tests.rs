@@ -143,3 +143,78 @@ fn test_representative_color_extraction() {
     assert!(representative.0[2] >= 195 && representative.0[2] <= 205);
     assert_eq!(representative.0[3], 255);
 }
+
+#[test]
+fn test_palette_quantizer_keeps_locked_colors() {
+    let mut pixels = Vec::new();
+    for i in 0..64u8 {
+        pixels.push(Rgba([i * 4, 100, 200 - i, 255]));
+    }
+
+    let locked = vec![Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255])];
+    for method in [QuantizationMethod::MedianCut, QuantizationMethod::KMeans] {
+        let quantizer = PaletteQuantizer::new(method, 4).with_locked_colors(locked.clone());
+        let palette = quantizer.build_palette(&pixels);
+
+        assert_eq!(palette.len(), 4);
+        assert_eq!(palette[0], Rgba([0, 0, 0, 255]));
+        assert_eq!(palette[1], Rgba([255, 255, 255, 255]));
+    }
+}
+
+#[test]
+fn test_palette_quantizer_locked_colors_fill_palette() {
+    let pixels = vec![Rgba([10, 200, 30, 255]); 8];
+    let locked = vec![Rgba([255, 0, 0, 255]), Rgba([255, 0, 0, 255])];
+
+    let quantizer =
+        PaletteQuantizer::new(QuantizationMethod::MedianCut, 1).with_locked_colors(locked);
+    let palette = quantizer.build_palette(&pixels);
+
+    // Duplicates are collapsed and no slots remain for derived colors
+    assert_eq!(palette, vec![Rgba([255, 0, 0, 255])]);
+}
+
+#[test]
+fn test_map_to_palette_nearest() {
+    let palette = vec![Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255])];
+
+    assert_eq!(
+        map_to_palette(&Rgba([20, 20, 20, 255]), &palette),
+        Rgba([0, 0, 0, 255])
+    );
+    assert_eq!(
+        map_to_palette(&Rgba([230, 230, 230, 255]), &palette),
+        Rgba([255, 255, 255, 255])
+    );
+    assert_eq!(
+        nearest_palette_index(&Rgba([240, 240, 240, 255]), &palette),
+        1
+    );
+}
+
+#[test]
+fn test_weighted_median_cut_matches_unit_weights() {
+    let pixels: Vec<Rgba<u8>> = (0..32u8)
+        .map(|i| Rgba([i * 8, 255 - i * 4, i * 2, 255]))
+        .collect();
+    let weighted: Vec<(Rgba<u8>, u32)> = pixels.iter().map(|&p| (p, 1)).collect();
+
+    assert_eq!(
+        MedianCutQuantizer::quantize(&pixels, 5),
+        MedianCutQuantizer::quantize_weighted(&weighted, 5)
+    );
+}
+
+#[test]
+fn test_weighted_median_cut_favors_heavy_colors() {
+    let colors = vec![
+        (Rgba([255, 0, 0, 255]), 1000),
+        (Rgba([0, 0, 255, 255]), 1),
+        (Rgba([0, 0, 250, 255]), 1),
+    ];
+
+    let palette = MedianCutQuantizer::quantize_weighted(&colors, 2);
+    assert_eq!(palette.len(), 2);
+    assert!(palette.contains(&Rgba([255, 0, 0, 255])));
+}
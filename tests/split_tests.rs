@@ -0,0 +1,151 @@
+use image::{Rgba, RgbaImage};
+use pixel_art_rust::core::integral::{IntegralImage, IntegralSpace};
+use pixel_art_rust::core::quadtree::{QuadNode, QuadTree, QuadTreeConfig};
+use pixel_art_rust::core::split::*;
+use std::sync::Arc;
+
+fn thin_line_image() -> RgbaImage {
+    let mut image = RgbaImage::new(32, 32);
+    for (x, _y, pixel) in image.enumerate_pixels_mut() {
+        *pixel = if x == 10 {
+            Rgba([90, 90, 90, 255])
+        } else {
+            Rgba([128, 128, 128, 255])
+        };
+    }
+    image
+}
+
+fn root_node(image: &RgbaImage, integral: &IntegralImage) -> QuadNode {
+    let (mean, variance) = integral.region_stats(0, 0, image.width(), image.height());
+    QuadNode::new(0, 0, image.width(), image.height(), mean, variance)
+}
+
+#[test]
+fn test_max_delta_catches_thin_lines() {
+    let image = thin_line_image();
+    let integral = IntegralImage::new(&image, IntegralSpace::Lab);
+    let context = SplitContext::new(&image, &integral);
+    let node = root_node(&image, &integral);
+
+    let variance = VarianceCriterion { threshold: 50.0 };
+    let max_delta = MaxDeltaCriterion { threshold: 10.0 };
+
+    assert!(!variance.should_split(&context, &node));
+    assert!(max_delta.should_split(&context, &node));
+}
+
+#[test]
+fn test_max_delta_is_the_largest_pairwise_distance() {
+    use pixel_art_rust::core::color::rgba_to_lab_values;
+
+    // Colors spread across all three Lab axes, so no pair spans the bounding box diagonal
+    let palette = [
+        Rgba([200, 60, 60, 255]),
+        Rgba([60, 180, 90, 255]),
+        Rgba([70, 110, 220, 255]),
+        Rgba([240, 220, 120, 255]),
+    ];
+    let image = RgbaImage::from_fn(6, 6, |x, y| palette[((x + 2 * y) % 4) as usize]);
+    let integral = IntegralImage::new(&image, IntegralSpace::Lab);
+    let context = SplitContext::new(&image, &integral);
+    let node = root_node(&image, &integral);
+
+    let labs: Vec<[f32; 3]> = palette.iter().map(rgba_to_lab_values).collect();
+    let distance = |a: &[f32; 3], b: &[f32; 3]| {
+        (0..3)
+            .map(|c| ((a[c] - b[c]) as f64).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    };
+    let mut largest: f64 = 0.0;
+    for a in &labs {
+        for b in &labs {
+            largest = largest.max(distance(a, b));
+        }
+    }
+    let diagonal = (0..3)
+        .map(|c| {
+            let values = labs.iter().map(|lab| lab[c] as f64);
+            let range = values.clone().fold(f64::MIN, f64::max) - values.fold(f64::MAX, f64::min);
+            range * range
+        })
+        .sum::<f64>()
+        .sqrt();
+    assert!(largest < diagonal - 1.0);
+    assert!((context.max_lab_delta(&node) - largest).abs() < 1e-6);
+
+    // A threshold between the real distance and the bounding box bound does not split
+    let between = MaxDeltaCriterion {
+        threshold: (largest + diagonal) / 2.0,
+    };
+    assert!(!between.should_split(&context, &node));
+    assert!(context.lab_delta_exceeds(&node, largest - 0.01));
+    assert!(!context.lab_delta_exceeds(&node, largest));
+}
+
+#[test]
+fn test_edge_energy_and_entropy() {
+    let flat = RgbaImage::from_pixel(8, 8, Rgba([40, 80, 120, 255]));
+    let mut halves = RgbaImage::new(8, 8);
+    for (x, _y, pixel) in halves.enumerate_pixels_mut() {
+        *pixel = if x < 4 {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        };
+    }
+
+    let flat_integral = IntegralImage::new(&flat, IntegralSpace::Lab);
+    let flat_context = SplitContext::new(&flat, &flat_integral);
+    let flat_node = root_node(&flat, &flat_integral);
+    assert!(flat_context.mean_edge_energy(&flat_node) < 1e-6);
+    assert!(flat_context.luminance_entropy(&flat_node, 16) < 1e-9);
+
+    let halves_integral = IntegralImage::new(&halves, IntegralSpace::Lab);
+    let halves_context = SplitContext::new(&halves, &halves_integral);
+    let halves_node = root_node(&halves, &halves_integral);
+    assert!(halves_context.mean_edge_energy(&halves_node) > 10.0);
+    assert!((halves_context.luminance_entropy(&halves_node, 16) - 1.0).abs() < 1e-9);
+
+    let edge = EdgeEnergyCriterion { threshold: 10.0 };
+    let entropy = EntropyCriterion {
+        threshold: 0.5,
+        bins: 16,
+    };
+    assert!(!edge.should_split(&flat_context, &flat_node));
+    assert!(edge.should_split(&halves_context, &halves_node));
+    assert!(!entropy.should_split(&flat_context, &flat_node));
+    assert!(entropy.should_split(&halves_context, &halves_node));
+}
+
+#[test]
+fn test_min_node_size_limits_leaves() {
+    let mut image = RgbaImage::new(20, 16);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        *pixel = Rgba([((x * 53) % 256) as u8, ((y * 97) % 256) as u8, 0, 255]);
+    }
+
+    let config = QuadTreeConfig::new(10, 0.0).with_min_node_size(4);
+    let tree = QuadTree::build_with_config(&image, &config);
+
+    let cells = tree.to_grid_cells();
+    assert!(cells.len() > 1);
+    for (_x, _y, w, h, _color) in cells {
+        assert!(w >= 4 && h >= 4);
+    }
+}
+
+#[test]
+fn test_quadtree_with_custom_criterion() {
+    let image = thin_line_image();
+
+    let by_variance = QuadTree::build(&image, 5, 50.0);
+    let config = QuadTreeConfig::new(5, 50.0)
+        .with_criterion(Arc::new(MaxDeltaCriterion { threshold: 10.0 }));
+    let by_delta = QuadTree::build_with_config(&image, &config);
+
+    assert_eq!(by_variance.node_count(), 1);
+    assert!(by_delta.node_count() > 1);
+    assert!((by_delta.variance_threshold - 10.0).abs() < f64::EPSILON);
+}
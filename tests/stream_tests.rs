@@ -0,0 +1,210 @@
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use pixel_art_rust::core::color::{AverageColorExtractor, MedianCutExtractor};
+use pixel_art_rust::core::error::PixelArtError;
+use pixel_art_rust::core::grid::{Grid, PixelAspect, Rect};
+use pixel_art_rust::core::pixel_art::PixelArtConverter;
+use pixel_art_rust::core::quantization::{PaletteQuantizer, QuantizationMethod};
+use pixel_art_rust::core::stream::*;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+
+fn gradient_image(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        Rgba([
+            (x * 7 % 256) as u8,
+            (y * 5 % 256) as u8,
+            ((x + y) * 3 % 256) as u8,
+            255,
+        ])
+    })
+}
+
+fn encode_png(image: &DynamicImage) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .unwrap();
+    bytes
+}
+
+fn stream_in_memory(converter: &PixelArtConverter, image: &RgbaImage) -> RgbaImage {
+    let mut source = ImageRowSource::new(image.clone());
+    let mut sink = ImageRowSink::new(image.width(), image.height());
+    converter.convert_streaming(&mut source, &mut sink).unwrap();
+    sink.into_image().unwrap()
+}
+
+#[test]
+fn test_streaming_matches_grid_conversion() {
+    // 8x6 cells over 50x40 leaves a remainder on both edges
+    let image = gradient_image(50, 40);
+    let grid = Grid::new(50, 40, 8, 6);
+    let converter = PixelArtConverter::with_grid(grid, Box::new(AverageColorExtractor));
+
+    let expected = converter
+        .convert(&DynamicImage::ImageRgba8(image.clone()))
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(stream_in_memory(&converter, &image), expected);
+}
+
+#[test]
+fn test_streaming_applies_palette_over_all_bands() {
+    let image = gradient_image(64, 48);
+    let grid = Grid::new(64, 48, 16, 12);
+    let mut converter =
+        PixelArtConverter::with_grid(grid, Box::new(MedianCutExtractor { max_colors: 8 }));
+    converter.set_palette_quantizer(PaletteQuantizer::new(QuantizationMethod::MedianCut, 4));
+
+    let expected = converter
+        .convert(&DynamicImage::ImageRgba8(image.clone()))
+        .unwrap()
+        .to_rgba8();
+    let streamed = stream_in_memory(&converter, &image);
+    assert_eq!(streamed, expected);
+
+    let mut colors: Vec<Rgba<u8>> = streamed.pixels().copied().collect();
+    colors.sort_by_key(|color| color.0);
+    colors.dedup();
+    assert!(colors.len() <= 4);
+}
+
+#[test]
+fn test_streaming_reports_every_cell() {
+    let image = gradient_image(30, 20);
+    let grid = Grid::new(30, 20, 5, 4);
+    let mut converter = PixelArtConverter::with_grid(grid, Box::new(AverageColorExtractor));
+    let cells = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&cells);
+    converter.set_progress_callback(Arc::new(move |row, col| {
+        recorded.lock().unwrap().push((row, col));
+    }));
+
+    stream_in_memory(&converter, &image);
+
+    let mut cells = cells.lock().unwrap().clone();
+    cells.sort();
+    let expected: Vec<(u32, u32)> = (0..4).flat_map(|r| (0..5).map(move |c| (r, c))).collect();
+    assert_eq!(cells, expected);
+}
+
+#[test]
+fn test_png_round_trip() {
+    let image = gradient_image(33, 27);
+    let grid = Grid::new(33, 27, 4, 4);
+    let converter = PixelArtConverter::with_grid(grid, Box::new(AverageColorExtractor));
+    let expected = converter
+        .convert(&DynamicImage::ImageRgba8(image.clone()))
+        .unwrap()
+        .to_rgba8();
+
+    // RGB input is widened to RGBA with full alpha while decoding
+    for input in [
+        DynamicImage::ImageRgba8(image.clone()),
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image.clone()).to_rgb8()),
+    ] {
+        let bytes = encode_png(&input);
+        let mut source = PngRowSource::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(source.dimensions(), (33, 27));
+
+        let path = std::env::temp_dir().join(format!(
+            "pixel_art_stream_{}_{:?}.png",
+            std::process::id(),
+            input.color()
+        ));
+        let mut sink = PngRowSink::create(&path, 33, 27).unwrap();
+        converter.convert_streaming(&mut source, &mut sink).unwrap();
+
+        let decoded = image::open(&path).unwrap().to_rgba8();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(decoded, expected);
+    }
+}
+
+#[test]
+fn test_png_source_expands_grayscale() {
+    let gray = image::GrayImage::from_fn(4, 2, |x, y| image::Luma([(x * 40 + y * 10) as u8]));
+    let bytes = encode_png(&DynamicImage::ImageLuma8(gray));
+    let mut source = PngRowSource::new(Cursor::new(bytes)).unwrap();
+
+    let mut row = vec![0u8; 16];
+    source.read_row(&mut row).unwrap();
+    source.read_row(&mut row).unwrap();
+    assert_eq!(&row[4..8], &[50, 50, 50, 255]);
+    assert!(matches!(
+        source.read_row(&mut row),
+        Err(PixelArtError::Io(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof
+    ));
+}
+
+#[test]
+fn test_streaming_requires_grid() {
+    let converter = PixelArtConverter::with_quadtree(4, 10.0, Box::new(AverageColorExtractor));
+    let mut source = ImageRowSource::new(gradient_image(8, 8));
+    let mut sink = ImageRowSink::new(8, 8);
+    assert!(matches!(
+        converter.convert_streaming(&mut source, &mut sink),
+        Err(PixelArtError::StreamingRequiresGrid)
+    ));
+}
+
+#[test]
+fn test_streaming_scaled_output() {
+    let image = gradient_image(48, 30);
+    let converter = PixelArtConverter::builder()
+        .cell_size(6, 5)
+        .scale(3)
+        .build()
+        .unwrap();
+
+    let (width, height) = converter.output_dimensions(48, 30).unwrap();
+    assert_eq!((width, height), (24, 18));
+
+    let mut source = ImageRowSource::new(image.clone());
+    let mut sink = ImageRowSink::new(width, height);
+    converter.convert_streaming(&mut source, &mut sink).unwrap();
+
+    let expected = converter
+        .convert(&DynamicImage::ImageRgba8(image))
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(sink.into_image().unwrap(), expected);
+}
+
+#[test]
+fn test_streaming_with_crop_and_offset() {
+    let image = gradient_image(60, 45);
+    let framed = PixelArtConverter::builder()
+        .cell_size(7, 6)
+        .crop(Rect::new(5, 4, 50, 38))
+        .offset(3, 2)
+        .build()
+        .unwrap();
+    let scaled = PixelArtConverter::builder()
+        .grid_cells(6, 5)
+        .crop(Rect::new(5, 4, 50, 38))
+        .offset(3, 2)
+        .scale(2)
+        .build()
+        .unwrap();
+    let wide = PixelArtConverter::builder()
+        .target_width(12)
+        .crop(Rect::new(5, 4, 50, 38))
+        .pixel_aspect(PixelAspect::new(3, 2))
+        .scale(2)
+        .build()
+        .unwrap();
+
+    for converter in [framed, scaled, wide] {
+        let (width, height) = converter.output_dimensions(60, 45).unwrap();
+        let mut source = ImageRowSource::new(image.clone());
+        let mut sink = ImageRowSink::new(width, height);
+        converter.convert_streaming(&mut source, &mut sink).unwrap();
+
+        let expected = converter
+            .convert(&DynamicImage::ImageRgba8(image.clone()))
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(sink.into_image().unwrap(), expected);
+    }
+}
@@ -0,0 +1,87 @@
+use image::{Rgba, RgbaImage};
+use pixel_art_rust::core::color::*;
+use pixel_art_rust::core::view::PixelView;
+
+// Every third diagonal shares one color so the most common color is never a tie
+fn test_image() -> RgbaImage {
+    RgbaImage::from_fn(7, 5, |x, y| {
+        if (x + y) % 3 == 0 {
+            Rgba([200, 40, 90, 255])
+        } else {
+            Rgba([
+                (x * 30) as u8,
+                (y * 50) as u8,
+                ((x * y) % 4 * 60) as u8,
+                255,
+            ])
+        }
+    })
+}
+
+fn copied_region(image: &RgbaImage, x: u32, y: u32, width: u32, height: u32) -> Vec<Rgba<u8>> {
+    let mut pixels = Vec::new();
+    for py in y..(y + height).min(image.height()) {
+        for px in x..(x + width).min(image.width()) {
+            pixels.push(*image.get_pixel(px, py));
+        }
+    }
+    pixels
+}
+
+#[test]
+fn test_view_rows_follow_image_stride() {
+    let image = test_image();
+    let view = PixelView::from_image(&image, 2, 1, 3, 2);
+
+    assert_eq!((view.width(), view.height(), view.len()), (3, 2, 6));
+    assert_eq!(view.row(1)[0], *image.get_pixel(2, 2));
+    assert_eq!(view.to_vec(), copied_region(&image, 2, 1, 3, 2));
+    assert!(view.as_slice().is_none());
+}
+
+#[test]
+fn test_view_clips_to_image() {
+    let image = test_image();
+
+    let view = PixelView::from_image(&image, 5, 3, 4, 4);
+    assert_eq!((view.width(), view.height()), (2, 2));
+    assert_eq!(view.to_vec(), copied_region(&image, 5, 3, 4, 4));
+
+    assert!(PixelView::from_image(&image, 7, 0, 2, 2).is_empty());
+    assert!(PixelView::from_image(&image, 0, 0, 0, 3).is_empty());
+}
+
+#[test]
+fn test_full_width_view_is_contiguous() {
+    let image = test_image();
+    let view = PixelView::from_image(&image, 0, 1, 7, 3);
+
+    let slice = view.as_slice().unwrap();
+    assert_eq!(slice, copied_region(&image, 0, 1, 7, 3).as_slice());
+}
+
+#[test]
+fn test_extractors_match_between_view_and_slice() {
+    let image = test_image();
+    let extractors: Vec<Box<dyn ColorExtractor>> = vec![
+        Box::new(AverageColorExtractor),
+        Box::new(MedianCutExtractor { max_colors: 8 }),
+        Box::new(KMeansExtractor {
+            k: 4,
+            max_iterations: 10,
+        }),
+        Box::new(SimdAverageColorExtractor),
+        Box::new(SoAAverageColorExtractor),
+    ];
+
+    for (x, y, width, height) in [(1, 1, 4, 3), (0, 0, 7, 5), (6, 4, 1, 1), (3, 2, 0, 2)] {
+        let view = PixelView::from_image(&image, x, y, width, height);
+        let copied = copied_region(&image, x, y, width, height);
+        for extractor in &extractors {
+            assert_eq!(
+                extractor.extract_color_view(view),
+                extractor.extract_color(&copied)
+            );
+        }
+    }
+}
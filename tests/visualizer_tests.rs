@@ -279,3 +279,18 @@ fn test_eta_calculation_accuracy() {
 
     visualizer.finish();
 }
+
+#[test]
+fn test_tree_visualizer_tracks_pixels() {
+    let visualizer = TreeVisualizer::new(100);
+    assert_eq!(visualizer.total_pixels(), 100);
+
+    visualizer.update(40, 100);
+    assert_eq!(visualizer.completed_pixels(), 40);
+
+    // The covered area can be smaller than the image, e.g. on a cell lattice
+    visualizer.update(90, 90);
+    assert_eq!(visualizer.total_pixels(), 90);
+    assert_eq!(visualizer.completed_pixels(), 90);
+    visualizer.finish();
+}